@@ -20,7 +20,7 @@ planes()
 obj((bunny))
 triangles()";
 
-    let scene = SCENE.get_or_init(|| config::parse(string));
+    let scene = SCENE.get_or_init(|| config::parse(string).unwrap());
 
     c.bench_function("rendering", |b| b.iter(|| scene.render()));
 }
@@ -34,7 +34,7 @@ planes()
 obj((bunny))
 triangles()";
 
-    let scene = SCENE.get_or_init(|| config::parse(string));
+    let scene = SCENE.get_or_init(|| config::parse(string).unwrap());
 
     c.bench_function("incremental_rendering", |b| b.iter(|| scene.render()));
 }
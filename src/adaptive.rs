@@ -0,0 +1,127 @@
+use crate::config::ParseError;
+
+/// Stops sampling the pixels whose noise fell below a threshold, so the passes after that go to the noisy ones.
+///
+/// The noise of a pixel is the relative error of its mean luminance, like 0.01 for 1%.
+/// A small minimum can end pixels before their rare, bright paths were found, which darkens them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    /// The noise below which a pixel is done
+    threshold: f64,
+    /// The samples every pixel gets, before its noise is trusted
+    min_samples: usize,
+    /// The samples the noisiest pixels get, replacing the samples per pixel of the screen
+    max_samples: usize,
+}
+impl AdaptiveSampling {
+    pub const fn min_samples(&self) -> usize {
+        self.min_samples
+    }
+    pub const fn max_samples(&self) -> usize {
+        self.max_samples
+    }
+    /// Renders up to `max_samples` instead, if it is still a multiple of the minimum samples
+    pub const fn with_max_samples(self, max_samples: usize) -> Option<Self> {
+        if max_samples < self.min_samples || !max_samples.is_multiple_of(self.min_samples) {
+            return None;
+        }
+
+        Some(Self {
+            max_samples,
+            ..self
+        })
+    }
+    /// If a pixel with `samples` samples and `noise` needs no further samples
+    pub fn is_converged(&self, samples: u64, noise: f64) -> bool {
+        samples >= self.min_samples as u64 && noise < self.threshold
+    }
+}
+impl TryFrom<&str> for AdaptiveSampling {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        const EXPECTED: &str = "a noise threshold, followed by the minimum and maximum samples";
+
+        let mut split = value.split_whitespace();
+
+        let threshold = split
+            .next()
+            .and_then(|threshold| threshold.parse().ok())
+            .filter(|&threshold: &f64| threshold > 0.)
+            .ok_or_else(|| ParseError::new(EXPECTED, value))?;
+        let mut samples = || {
+            split
+                .next()
+                .and_then(|samples| samples.parse().ok())
+                .ok_or_else(|| ParseError::new(EXPECTED, value))
+        };
+        let min_samples: usize = samples()?;
+        let max_samples: usize = samples()?;
+
+        // the noise can only be estimated from two samples on
+        if min_samples < 2 {
+            return Err(ParseError::new("a minimum of at least 2 samples", value));
+        }
+        // the minimum is the size of the passes, unless incremental sets it
+        if max_samples < min_samples || !max_samples.is_multiple_of(min_samples) {
+            return Err(ParseError::new(
+                "a maximum that is a multiple of the minimum samples",
+                value,
+            ));
+        }
+
+        // no trailing parameters
+        match split.next() {
+            None => Ok(Self {
+                threshold,
+                min_samples,
+                max_samples,
+            }),
+            Some(_) => Err(ParseError::new("no further adaptive parameters", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let adaptive = AdaptiveSampling::try_from("0.01 16 256").unwrap();
+        assert_eq!((adaptive.min_samples(), adaptive.max_samples()), (16, 256));
+
+        for invalid in [
+            "",
+            "0 16 256",
+            "0.01 16",
+            "0.01 1 256",
+            "0.01 16 250",
+            "0.01 16 8",
+            "0.01 16 256 512",
+        ] {
+            assert!(AdaptiveSampling::try_from(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn convergence() {
+        let adaptive = AdaptiveSampling::try_from("0.01 16 256").unwrap();
+        assert!(adaptive.is_converged(16, 0.005));
+        assert!(!adaptive.is_converged(8, 0.005));
+        assert!(!adaptive.is_converged(32, 0.02));
+    }
+
+    #[test]
+    fn max_samples() {
+        let adaptive = AdaptiveSampling::try_from("0.01 16 256").unwrap();
+        assert_eq!(
+            adaptive
+                .with_max_samples(64)
+                .map(|adaptive| adaptive.max_samples()),
+            Some(64)
+        );
+        assert_eq!(adaptive.with_max_samples(8), None);
+        assert_eq!(adaptive.with_max_samples(40), None);
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use crate::{
+    Format, Output, Scene, ShapeIndex,
+    config::ParseError,
+    shapes::MaterialIndexer,
+    vec3::{NormalizedVector3, Point3},
+};
+
+/// Arbitrary output variables, the data of the first hit written as pfm images next to the image.
+/// Rays leaving the scene are 0, apart from the ids, which are -1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    /// The color of the material
+    Albedo,
+    /// The shading normal
+    Normal,
+    /// The distance from the camera
+    Depth,
+    /// The hit point in world space
+    Position,
+    /// The shape and the material, in red and green.
+    /// As ids can't be averaged, they are taken from the first sample of the pixel.
+    Id,
+}
+impl Aov {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Albedo => "albedo",
+            Self::Normal => "normal",
+            Self::Depth => "depth",
+            Self::Position => "position",
+            Self::Id => "id",
+        }
+    }
+    /// Where the aov of the image at `output` is written, replacing its extension with `name.pfm`
+    pub fn output(self, output: &Output) -> Output {
+        Output {
+            path: Path::new(&output.path)
+                .with_extension(format!("{}.pfm", self.name()))
+                .to_string_lossy()
+                .into_owned(),
+            format: Format::Pfm,
+        }
+    }
+    /// If the samples of a pixel are averaged, otherwise the first one is kept
+    pub const fn is_averaged(self) -> bool {
+        !matches!(self, Self::Id)
+    }
+
+    /// The value of the aov for the closest intersection of a camera ray
+    #[expect(clippy::type_complexity)]
+    pub(crate) fn value(
+        self,
+        scene: &Scene,
+        intersection: Option<&(
+            (f32, Point3, (NormalizedVector3, [f32; 2]), MaterialIndexer),
+            ShapeIndex,
+        )>,
+    ) -> [f32; 3] {
+        let Some(&((distance, hit_point, (normal, texture_coordinates), material_index), shape)) =
+            intersection
+        else {
+            return if self == Self::Id { [-1.; 3] } else { [0.; 3] };
+        };
+
+        match self {
+            Self::Albedo => material_index
+                .index(&*scene.materials)
+                .albedo(texture_coordinates)
+                .into_inner(),
+            Self::Normal => normal.into_inner(),
+            Self::Depth => [distance; 3],
+            Self::Position => hit_point.into_inner(),
+            #[expect(clippy::cast_precision_loss)]
+            Self::Id => [
+                shape.id(&scene.shapes) as f32,
+                material_index.inner() as f32,
+                0.,
+            ],
+        }
+    }
+}
+impl TryFrom<&str> for Aov {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "albedo" => Ok(Self::Albedo),
+            "normal" => Ok(Self::Normal),
+            "depth" => Ok(Self::Depth),
+            "position" => Ok(Self::Position),
+            "id" => Ok(Self::Id),
+            other => Err(ParseError::new(
+                "one of albedo, normal, depth, position or id",
+                other,
+            )),
+        }
+    }
+}
@@ -224,12 +224,38 @@ impl<T: Shape> BvhNode<T> {
     }
     /// Returns the closest shape that intersects with the ray, alongside the distance
     #[inline(always)]
+    #[expect(clippy::type_complexity)]
     pub fn closest_shape(
         ray: &Ray,
         shapes: &[T],
         nodes: &[Self],
         stack: &mut Vec<(f32, BvhNodeIndexerType)>,
-    ) -> Option<(f32, Point3, (NormalizedVector3, [f32; 2]), MaterialIndexer)> {
+    ) -> Option<(
+        f32,
+        Point3,
+        (NormalizedVector3, [f32; 2]),
+        MaterialIndexer,
+        ShapesIndexer<T>,
+    )> {
+        // optimized away when inlined
+        Self::closest_shape_counting(ray, shapes, nodes, stack, &mut [0; 2])
+    }
+    /// `closest_shape`, adding the amount of visited nodes and intersection tested shapes to `visits`
+    #[inline(always)]
+    #[expect(clippy::type_complexity)]
+    pub fn closest_shape_counting(
+        ray: &Ray,
+        shapes: &[T],
+        nodes: &[Self],
+        stack: &mut Vec<(f32, BvhNodeIndexerType)>,
+        visits: &mut [u32; 2],
+    ) -> Option<(
+        f32,
+        Point3,
+        (NormalizedVector3, [f32; 2]),
+        MaterialIndexer,
+        ShapesIndexer<T>,
+    )> {
         stack.clear();
         // SAFETY:
         // - Indexer is a repr(transparent) wrapper around IndexerType
@@ -254,6 +280,7 @@ impl<T: Shape> BvhNode<T> {
             }
 
             let node = entry.1.index(nodes);
+            visits[0] += 1;
 
             match node.kind {
                 Branch { children } => {
@@ -287,6 +314,7 @@ impl<T: Shape> BvhNode<T> {
                 }
                 Leaf { shapes_range } => {
                     for index in shapes_range {
+                        visits[1] += 1;
                         if let Some(time) = index.index(shapes).intersects(ray)
                             && time < closest_hit.0
                         {
@@ -309,6 +337,7 @@ impl<T: Shape> BvhNode<T> {
                     .index(shapes)
                     .normal_and_texture_coordinates(&hit_point),
                 index.index(shapes).material_index(),
+                index,
             )
         })
     }
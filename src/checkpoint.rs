@@ -0,0 +1,115 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs, io,
+};
+
+use crate::Output;
+
+/// The state of a render next to its accumulation file, which holds the radiance and the samples of every pixel.
+///
+/// Every sample seeds its random numbers from the render seed, its pixel and its index,
+/// so the seed and the sample counts are the whole rng state.
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    pub scene_hash: u64,
+    pub seed: u64,
+    /// The samples per pixel of all completed passes
+    pub samples: u64,
+}
+impl Checkpoint {
+    pub fn path(output: &Output) -> String {
+        format!("{}.checkpoint", output.path)
+    }
+    /// # Errors
+    /// When the file can't be written
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        // a crash while writing must not leave a truncated checkpoint behind
+        let temporary = format!("{path}.tmp");
+        fs::write(
+            &temporary,
+            format!(
+                "hash {}\nseed {}\nsamples {}\n",
+                self.scene_hash, self.seed, self.samples
+            ),
+        )?;
+        fs::rename(temporary, path)
+    }
+    /// # Errors
+    /// When the file can't be read or isn't a valid checkpoint
+    pub fn read(path: &str) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut lines = contents.lines().map(|line| line.split_once(' '));
+
+        let mut value = |name| match lines.next() {
+            Some(Some((key, value))) if key == name => value.parse().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid checkpoint value")
+            }),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a valid checkpoint",
+            )),
+        };
+
+        Ok(Self {
+            scene_hash: value("hash")?,
+            seed: value("seed")?,
+            samples: value("samples")?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ResumeError {
+    Io(io::Error),
+    /// The scene or the settings affecting the image changed since the checkpoint
+    SceneChanged,
+}
+impl Display for ResumeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Io(ref error) => write!(f, "could not resume: {error}"),
+            Self::SceneChanged => write!(
+                f,
+                "could not resume: the scene changed since the checkpoint"
+            ),
+        }
+    }
+}
+impl Error for ResumeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn round_trip() {
+        let directory = TempDir::new("checkpoint-round-trip");
+        let path = directory.join("out.checkpoint");
+        let checkpoint = Checkpoint {
+            scene_hash: u64::MAX,
+            seed: 7,
+            samples: 128,
+        };
+
+        checkpoint.write(&path).unwrap();
+        assert_eq!(Checkpoint::read(&path).unwrap(), checkpoint);
+    }
+
+    #[test]
+    fn invalid() {
+        let directory = TempDir::new("checkpoint-invalid");
+
+        for contents in [
+            "",
+            "hash 1\nseed 2\n",
+            "seed 2\nhash 1\nsamples 3\n",
+            "hash 1\nseed -2\nsamples 3\n",
+        ] {
+            let path = directory.write("out.checkpoint", contents);
+            let error = Checkpoint::read(&path).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{contents:?}");
+        }
+    }
+}
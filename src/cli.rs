@@ -0,0 +1,338 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    num::NonZeroUsize,
+    str::FromStr,
+};
+
+use crate::{Format, Output, Scene, termination::TimeLimit};
+
+pub const USAGE: &str = "Usage: raytracer [OPTIONS] [SCENE]
+
+Arguments:
+  [SCENE]  The scene file to render [default: scene]
+
+Options:
+  -o, --output <PATH>           The image to write to [default: target/out.ppm]
+  -f, --format <FORMAT>         ppm or pfm [default: inferred from the output path]
+  -r, --resolution <WxH>        Overrides the resolution of the screen
+  -s, --samples <N>             Overrides the samples per pixel, or the maximum of adaptive sampling
+  -b, --bounces <N>             Overrides the maximum amount of bounces
+  -t, --threads <N>             The amount of threads to render with [default: all cores]
+  -i, --incremental <N>         Overrides the amount of samples per incremental pass
+  -c, --continue <N>            Continues a render that already has N samples per pixel
+      --time-limit <DURATION>   Stops the render after a duration, like 90s, 20m or 1.5h, once the first pass is done
+      --resume                  Resumes the render from its checkpoint
+  -h, --help                    Prints this message";
+
+/// The parsed command line arguments
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub scene: String,
+    pub output: Output,
+    pub resolution: Option<[usize; 2]>,
+    pub samples_per_pixel: Option<usize>,
+    pub max_bounces: Option<usize>,
+    pub threads: Option<NonZeroUsize>,
+    pub incremental: Option<usize>,
+    pub continue_sampling: Option<usize>,
+    pub time_limit: Option<TimeLimit>,
+    pub resume: bool,
+}
+impl Args {
+    /// Parses the arguments, excluding the program name
+    /// # Errors
+    /// When an argument is unknown or has an invalid value, or help was requested
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut scene = None;
+        let mut output = None;
+        let mut format = None;
+        let mut resolution = None;
+        let mut samples_per_pixel = None;
+        let mut max_bounces = None;
+        let mut threads = None;
+        let mut incremental = None;
+        let mut continue_sampling = None;
+        let mut time_limit = None;
+        let mut resume = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // positional argument
+            if !arg.starts_with('-') {
+                if scene.replace(arg.clone()).is_some() {
+                    return Err(CliError::Unexpected(arg));
+                }
+                continue;
+            }
+
+            // support both `--flag value` and `--flag=value`
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_owned(), Some(value.to_owned())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliError::MissingValue(flag.clone()))
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => return Err(CliError::Help),
+                "--resume" => resume = true,
+                "-o" | "--output" => output = Some(value()?),
+                "-f" | "--format" => format = Some(parse_value(&flag, &value()?)?),
+                "-r" | "--resolution" => {
+                    let value = value()?;
+                    let parsed = value
+                        .split_once('x')
+                        .map(|(width, height)| (width.parse(), height.parse()));
+                    // the screen needs at least two pixels per axis
+                    let Some((Ok(width @ 2..), Ok(height @ 2..))) = parsed else {
+                        return Err(CliError::InvalidValue { flag, value });
+                    };
+                    resolution = Some([width, height]);
+                }
+                "-s" | "--samples" => {
+                    samples_per_pixel = Some(parse_value::<NonZeroUsize>(&flag, &value()?)?.get());
+                }
+                "-b" | "--bounces" => max_bounces = Some(parse_value(&flag, &value()?)?),
+                "-t" | "--threads" => threads = Some(parse_value(&flag, &value()?)?),
+                "-i" | "--incremental" => incremental = Some(parse_value(&flag, &value()?)?),
+                "-c" | "--continue" => continue_sampling = Some(parse_value(&flag, &value()?)?),
+                "--time-limit" => time_limit = Some(parse_value(&flag, &value()?)?),
+                _ => return Err(CliError::Unexpected(flag)),
+            }
+        }
+
+        let output = output.unwrap_or_else(|| "target/out.ppm".to_owned());
+        let format = format.unwrap_or_else(|| Format::from_path(&output));
+
+        Ok(Self {
+            scene: scene.unwrap_or_else(|| "scene".to_owned()),
+            output: Output {
+                path: output,
+                format,
+            },
+            resolution,
+            samples_per_pixel,
+            max_bounces,
+            threads,
+            incremental,
+            continue_sampling,
+            time_limit,
+            resume,
+        })
+    }
+    /// Applies the overrides to `scene`, leaving it unchanged on an error
+    /// # Errors
+    /// When the incremental amount does not divide the samples per pixel,
+    /// or the samples are not a multiple of the minimum samples of adaptive sampling
+    pub fn apply(&self, scene: &mut Scene) -> Result<(), CliError> {
+        let samples_per_pixel = self
+            .samples_per_pixel
+            .unwrap_or(scene.screen.samples_per_pixel);
+        // adaptive sampling renders up to its maximum, so the samples replace it
+        let adaptive = match (scene.adaptive, self.samples_per_pixel) {
+            (Some(adaptive), Some(samples_per_pixel)) => Some(
+                adaptive
+                    .with_max_samples(samples_per_pixel)
+                    .ok_or_else(|| CliError::InvalidValue {
+                        flag: "--samples".to_owned(),
+                        value: samples_per_pixel.to_string(),
+                    })?,
+            ),
+            (adaptive, _) => adaptive,
+        };
+        let incremental = self.incremental.or(scene.incremental);
+        if let Some(incremental) = incremental
+            && (incremental == 0 || !samples_per_pixel.is_multiple_of(incremental))
+        {
+            return Err(CliError::InvalidValue {
+                flag: "--incremental".to_owned(),
+                value: incremental.to_string(),
+            });
+        }
+
+        scene.output = self.output.clone();
+        scene.threads = self.threads;
+        if let Some(resolution) = self.resolution {
+            scene.screen.resize(resolution, &scene.camera);
+        }
+        scene.screen.samples_per_pixel = samples_per_pixel;
+        scene.adaptive = adaptive;
+        if let Some(max_bounces) = self.max_bounces {
+            scene.screen.max_bounces = max_bounces;
+        }
+        scene.incremental = incremental;
+        if let Some(continue_sampling) = self.continue_sampling {
+            scene.continue_sampling = Some(continue_sampling);
+        }
+        if let Some(time_limit) = self.time_limit {
+            scene.termination.time_limit = Some(time_limit);
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, CliError> {
+    value.parse().map_err(|_| CliError::InvalidValue {
+        flag: flag.to_owned(),
+        value: value.to_owned(),
+    })
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    /// Help was requested
+    Help,
+    Unexpected(String),
+    MissingValue(String),
+    InvalidValue {
+        flag: String,
+        value: String,
+    },
+}
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Help => write!(f, "{USAGE}"),
+            Self::Unexpected(ref arg) => write!(f, "unexpected argument `{arg}`"),
+            Self::MissingValue(ref flag) => write!(f, "missing value for `{flag}`"),
+            Self::InvalidValue {
+                ref flag,
+                ref value,
+            } => write!(f, "invalid value `{value}` for `{flag}`"),
+        }
+    }
+}
+impl Error for CliError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config,
+        testing::SCENE,
+        vec3::{New as _, Point3, Vector3},
+    };
+
+    fn parse(args: &[&str]) -> Result<Args, CliError> {
+        Args::parse(args.iter().map(|&arg| arg.to_owned()))
+    }
+
+    #[test]
+    fn defaults() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.scene, "scene");
+        assert_eq!(args.output.path, "target/out.ppm");
+        assert_eq!(args.samples_per_pixel, None);
+    }
+
+    #[test]
+    fn inline_and_separate_values() {
+        let args = parse(&["-s", "16", "--resolution=40x30", "other"]).unwrap();
+        assert_eq!(args.samples_per_pixel, Some(16));
+        assert_eq!(args.resolution, Some([40, 30]));
+        assert_eq!(args.scene, "other");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(&["-h"]), Err(CliError::Help));
+        assert_eq!(
+            parse(&["--unknown"]),
+            Err(CliError::Unexpected("--unknown".to_owned()))
+        );
+        assert_eq!(
+            parse(&["a", "b"]),
+            Err(CliError::Unexpected("b".to_owned()))
+        );
+        assert_eq!(
+            parse(&["--samples"]),
+            Err(CliError::MissingValue("--samples".to_owned()))
+        );
+        for (flag, value) in [
+            ("-s", "many"),
+            ("-s", "0"),
+            ("-r", "1x100"),
+            ("-r", "100"),
+            ("-t", "0"),
+        ] {
+            assert_eq!(
+                parse(&[flag, value]),
+                Err(CliError::InvalidValue {
+                    flag: flag.to_owned(),
+                    value: value.to_owned(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn incremental_divides_samples() {
+        let mut scene = config::parse(SCENE).unwrap();
+        assert_eq!(parse(&["-i", "4"]).unwrap().apply(&mut scene), Ok(()));
+        assert_eq!(
+            parse(&["-i", "3"]).unwrap().apply(&mut scene),
+            Err(CliError::InvalidValue {
+                flag: "--incremental".to_owned(),
+                value: "3".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_overrides_leave_the_scene_unchanged() {
+        let mut scene = config::parse(SCENE).unwrap();
+        assert_eq!(
+            parse(&["-r", "8x8", "-b", "5", "-i", "3"])
+                .unwrap()
+                .apply(&mut scene),
+            Err(CliError::InvalidValue {
+                flag: "--incremental".to_owned(),
+                value: "3".to_owned(),
+            })
+        );
+        assert_eq!(scene.screen.resolution_width, 4);
+        assert_eq!(scene.screen.max_bounces, 2);
+        assert_eq!(scene.incremental, None);
+    }
+
+    #[test]
+    fn resolution_keeps_aspect_ratio_of_screen_corners() {
+        let mut scene = config::parse(SCENE).unwrap();
+        parse(&["-r", "8x8"]).unwrap().apply(&mut scene).unwrap();
+        assert_eq!(scene.screen.top_left, Point3::new([-1., 1., -0.5]));
+        assert_eq!(scene.screen.top_edge, Vector3::new([2., 0., 0.]));
+
+        // twice as wide around the center
+        parse(&["-r", "16x8"]).unwrap().apply(&mut scene).unwrap();
+        assert_eq!(scene.screen.top_left, Point3::new([-2., 1., -0.5]));
+        assert_eq!(scene.screen.top_edge, Vector3::new([4., 0., 0.]));
+        assert_eq!(scene.screen.left_edge, Vector3::new([0., -2., 0.]));
+    }
+
+    #[test]
+    fn samples_replace_adaptive_maximum() {
+        let mut scene = config::parse(&format!("{SCENE}\nadaptive(0.01 4 64)")).unwrap();
+        parse(&["-s", "32"]).unwrap().apply(&mut scene).unwrap();
+        assert_eq!(scene.screen.samples_per_pixel, 32);
+        assert_eq!(
+            scene.adaptive.map(|adaptive| adaptive.max_samples()),
+            Some(32)
+        );
+
+        for samples in ["2", "30"] {
+            assert_eq!(
+                parse(&["-s", samples]).unwrap().apply(&mut scene),
+                Err(CliError::InvalidValue {
+                    flag: "--samples".to_owned(),
+                    value: samples.to_owned(),
+                })
+            );
+        }
+    }
+}
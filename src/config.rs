@@ -1,137 +1,526 @@
-use std::str::Split;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str::{FromStr, Split},
+};
 
 use crate::{
-    Bvhs, Camera, Plane, Scene, Screen, Shapes, Sphere,
+    Bvhs, Camera, Lens, LookAt, Plane, Projection, Scene, Screen, Shapes, Sphere,
+    adaptive::AdaptiveSampling,
+    aov::Aov,
     bvh::BvhNode,
     convert::Convert,
+    denoise::Denoiser,
+    filter::{Filter, FilterKind},
     indices::{HasIndexer, Indexer},
-    material::{ColorKind, Material},
-    obj,
+    integrator::{self, Integrator, Path},
+    light::Light,
+    material::{ColorKind, Material, MaterialKind},
+    obj::{self, ObjError},
+    rng,
+    sampler::SamplerKind,
     shapes::{MaterialIndexer, NormalsTextureCoordinates, Triangle},
-    vec3::Vector3,
+    termination::Termination,
+    vec3::{New as _, Point3, Vector3},
 };
 
-#[expect(clippy::too_many_lines)]
-pub fn parse(string: &str) -> Scene {
-    let mut iter = string.lines();
+/// A value that did not have the expected form
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub expected: &'static str,
+    pub found: String,
+}
+impl ParseError {
+    pub fn new(expected: &'static str, found: &str) -> Self {
+        Self {
+            expected,
+            found: found.to_owned(),
+        }
+    }
+}
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {}, found `{}`", self.expected, self.found)
+    }
+}
+impl Error for ParseError {}
+
+/// An error in a scene file, pointing at the offending entry
+#[derive(Debug)]
+pub struct ConfigError {
+    /// 1-based
+    pub line: usize,
+    /// 1-based
+    pub column: usize,
+    /// The name of the entry, e.g. `screen` or `spheres`
+    pub entry: String,
+    pub kind: ConfigErrorKind,
+}
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match self.kind {
+            ConfigErrorKind::Parse(ref error) => write!(f, "in `{}`: {error}", self.entry),
+            ConfigErrorKind::UnknownEntry => write!(f, "unknown entry `{}`", self.entry),
+            ConfigErrorKind::MissingEntry => write!(f, "missing entry `{}`", self.entry),
+            ConfigErrorKind::Obj(ref error) => write!(f, "in `{}`: {error}", self.entry),
+        }
+    }
+}
+impl Error for ConfigError {}
+
+#[derive(Debug)]
+pub enum ConfigErrorKind {
+    Parse(ParseError),
+    UnknownEntry,
+    MissingEntry,
+    Obj(ObjError),
+}
+
+/// Returns the 1-based line number of `line`, which has to be a subslice of `string`
+pub fn line_number(string: &str, line: &str) -> Option<usize> {
+    string
+        .substr_range(line)
+        .map(|range| string[..range.start].matches('\n').count() + 1)
+}
+
+/// The line that is currently being parsed, used for locating errors
+#[derive(Clone, Copy)]
+struct Context<'a> {
+    line_number: usize,
+    line: &'a str,
+    entry: &'a str,
+}
+impl Context<'_> {
+    /// `at` has to be a subslice of the line
+    fn error(&self, at: &str, kind: ConfigErrorKind) -> ConfigError {
+        ConfigError {
+            line: self.line_number,
+            column: self
+                .line
+                .substr_range(at)
+                .map_or(self.line.len(), |range| range.start)
+                + 1,
+            entry: self.entry.to_owned(),
+            kind,
+        }
+    }
+    fn parse_error(&self, at: &str, expected: &'static str) -> ConfigError {
+        self.error(at, ConfigErrorKind::Parse(ParseError::new(expected, at)))
+    }
+    fn parse_value<T: FromStr>(
+        &self,
+        value: &str,
+        expected: &'static str,
+    ) -> Result<T, ConfigError> {
+        value.parse().map_err(|_| self.parse_error(value, expected))
+    }
+}
+
+/// The comma separated values of a single item
+struct Values<'a> {
+    split: Split<'a, &'static str>,
+    /// All values, used for errors at the end of the item
+    all: &'a str,
+    context: Context<'a>,
+}
+impl<'a> Values<'a> {
+    fn next_str(&mut self, expected: &'static str) -> Result<&'a str, ConfigError> {
+        self.split.next().ok_or_else(|| {
+            self.context
+                .parse_error(&self.all[self.all.len()..], expected)
+        })
+    }
+    /// Parses the next value using `f`
+    fn next_with<T>(
+        &mut self,
+        f: impl FnOnce(&'a str) -> Result<T, ParseError>,
+    ) -> Result<T, ConfigError> {
+        let value = self.next_str("another value")?;
+
+        f(value).map_err(|error| self.context.error(value, ConfigErrorKind::Parse(error)))
+    }
+    /// Parses the next value using `FromStr`
+    fn next<T: FromStr>(&mut self, expected: &'static str) -> Result<T, ConfigError> {
+        let value = self.next_str(expected)?;
 
+        value
+            .parse()
+            .map_err(|_| self.context.parse_error(value, expected))
+    }
+}
+
+/// Parses a scene description
+/// # Errors
+/// When the scene description is invalid, pointing at the offending entry
+#[expect(clippy::too_many_lines)]
+pub fn parse(string: &str) -> Result<Scene, ConfigError> {
     // init values
     let mut incremental = None;
     let mut continue_sampling = None;
+    let mut seed = 0;
+    let mut sampler = SamplerKind::Independent;
+    let mut filter = Filter::default();
+    let mut integrator: Box<dyn Integrator> = Box::new(Path::default());
+    let mut russian_roulette = None;
+    let mut aovs = Vec::new();
+    let mut denoiser = None;
+    let mut adaptive = None;
+    let mut termination = Termination::default();
+    // set if the noise target is given
+    let mut noise_target_context = None;
     let mut screen = None;
+    // set if the screen corners are omitted
+    let mut screen_context = None;
     let mut camera = None;
+    let mut lens = None;
+    let mut projection = None;
     let mut spheres = None;
     let mut planes = None;
     let mut triangles = None;
     let mut normals = Vec::new();
     let mut texture_coordinates = Vec::new();
     let mut barycentric_precomputed = Vec::new();
-    let mut materials = Interner(Vec::new());
+    // of the obj and mtl files
+    let mut file_hashes = Vec::new();
+    let mut materials = Interner::default();
 
     // parse
-    while screen.is_none()
-        | camera.is_none()
-        | spheres.is_none()
-        | planes.is_none()
-        | triangles.is_none()
-    {
-        let next = iter.next().unwrap();
+    for (index, line) in string.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut context = Context {
+            line_number: index + 1,
+            line,
+            entry: line,
+        };
+
         // split into field and value
-        match next[..next.len() - 1].split_once('(').unwrap() {
-            ("continue", value) => {
-                continue_sampling = Some(value.parse().unwrap());
-            }
-            ("incremental", value) => {
-                incremental = Some(value.parse().unwrap());
-            }
-            ("screen", value) => {
-                screen = Some(single_item_parse(value, |values| {
-                    Screen::new(
-                        values.next().unwrap().into(),
-                        values.next().unwrap().into(),
-                        values.next().unwrap().into(),
-                        values.next().unwrap().parse().unwrap(),
-                        values.next().unwrap().parse().unwrap(),
-                        values.next().unwrap().parse().unwrap(),
-                        values.next().unwrap().parse().unwrap(),
-                    )
-                }));
-            }
-            ("camera", value) => camera = Some(Camera::new(value[..value.len()].into())),
-            ("spheres", value) => {
-                spheres = Some(multi_item_parse(value, |values| {
-                    Sphere::new(
-                        values.next().unwrap().into(),
-                        values.next().unwrap().parse().unwrap(),
-                        push_material_with_values(values, &mut materials),
-                    )
-                }));
-            }
-            ("planes", value) => {
-                planes = Some(multi_item_parse(value, |values| {
-                    Plane::new(
-                        values.next().unwrap().into(),
-                        Vector3::normalize(values.next().unwrap().into()),
-                        push_material_with_values(values, &mut materials),
-                    )
-                }));
+        let Some((entry, value)) = line.strip_suffix(')').and_then(|line| line.split_once('('))
+        else {
+            return Err(context.parse_error(line, "an entry of the form `name(values)`"));
+        };
+        context.entry = entry;
+
+        match entry {
+            "continue" => {
+                continue_sampling = Some(context.parse_value(value, "a number of samples")?);
+            }
+            "seed" => {
+                seed = context.parse_value(value, "a seed between 0 and 2^64 - 1")?;
+            }
+            "sampler" => {
+                sampler = SamplerKind::try_from(value)
+                    .map_err(|error| context.error(value, ConfigErrorKind::Parse(error)))?;
             }
-            ("triangles", value) => {
+            "filter" => {
+                filter = Filter::new(
+                    FilterKind::try_from(value)
+                        .map_err(|error| context.error(value, ConfigErrorKind::Parse(error)))?,
+                );
+            }
+            "integrator" => {
+                integrator = integrator::parse(value)
+                    .map_err(|error| context.error(value, ConfigErrorKind::Parse(error)))?;
+            }
+            "aovs" => {
+                aovs = value
+                    .split(", ")
+                    .map(Aov::try_from)
+                    .collect::<Result<_, _>>()
+                    .map_err(|error| context.error(value, ConfigErrorKind::Parse(error)))?;
+            }
+            "denoise" => {
+                denoiser = Some(
+                    Denoiser::try_from(value)
+                        .map_err(|error| context.error(value, ConfigErrorKind::Parse(error)))?,
+                );
+            }
+            "adaptive" => {
+                adaptive = Some(
+                    AdaptiveSampling::try_from(value)
+                        .map_err(|error| context.error(value, ConfigErrorKind::Parse(error)))?,
+                );
+            }
+            "time_limit" => {
+                termination.time_limit =
+                    Some(context.parse_value(value, "a duration, like 90s, 20m or 1.5h")?);
+            }
+            "noise_target" => {
+                let target = context.parse_value(value, "a positive noise target")?;
+                if target <= 0. {
+                    return Err(context.parse_error(value, "a positive noise target"));
+                }
+                termination.noise_target = Some(target);
+                noise_target_context = Some((context, value));
+            }
+            "russian_roulette" => {
+                russian_roulette = Some(context.parse_value(value, "a minimum number of bounces")?);
+            }
+            "incremental" => {
+                let amount = context.parse_value(value, "a non-zero number of samples")?;
+                if amount == 0 {
+                    return Err(context.parse_error(value, "a non-zero number of samples"));
+                }
+                incremental = Some((amount, context, value));
+            }
+            "screen" => {
+                screen = Some(single_item_parse(value, context, |values| {
+                    // the corners are derived from a look-at camera if they are omitted
+                    let (top_left, top_edge, left_edge) = if value.split(", ").count() == 4 {
+                        screen_context = Some((context, value));
+                        (Point3::new([0.; 3]), Vector3::default(), Vector3::default())
+                    } else {
+                        (
+                            values.next_with(Point3::try_from)?,
+                            values.next_with(Vector3::try_from)?,
+                            values.next_with(Vector3::try_from)?,
+                        )
+                    };
+
+                    Ok(Screen::new(
+                        top_left,
+                        top_edge,
+                        left_edge,
+                        values.next("a resolution width")?,
+                        values.next("a resolution height")?,
+                        values.next("a number of samples per pixel")?,
+                        values.next("a maximum number of bounces")?,
+                    ))
+                })?);
+            }
+            "camera" => {
+                camera = Some(single_item_parse(value, context, |values| {
+                    let position = values.next_with(Point3::try_from)?;
+
+                    // look_from, look_at, up, vertical fov
+                    let look_at = if value.contains(", ") {
+                        let target = values.next_with(Point3::try_from)?;
+                        let up_str = values.next_str("an up vector")?;
+                        let up = Vector3::try_from(up_str).map_err(|error| {
+                            context.error(up_str, ConfigErrorKind::Parse(error))
+                        })?;
+                        let fov_str = values.next_str("a vertical field of view in degrees")?;
+                        let vertical_fov: f32 =
+                            context.parse_value(fov_str, "a vertical field of view in degrees")?;
+
+                        if vertical_fov <= 0. || vertical_fov >= 180. {
+                            return Err(context.parse_error(
+                                fov_str,
+                                "a vertical field of view between 0 and 180 degrees",
+                            ));
+                        }
+                        if position.vector_to(target).cross(up).near_zero() {
+                            return Err(context.parse_error(
+                                up_str,
+                                "an up vector not parallel to the view direction",
+                            ));
+                        }
+
+                        Some(LookAt::new(target, up, vertical_fov))
+                    } else {
+                        None
+                    };
+
+                    Ok(Camera::new(position, look_at))
+                })?);
+            }
+            "lens" => {
+                lens = Some(single_item_parse(value, context, |values| {
+                    let aperture_radius: f32 = values.next("an aperture radius")?;
+                    if aperture_radius < 0. {
+                        return Err(context.parse_error(value, "a non-negative aperture radius"));
+                    }
+                    // defaults to the distance to the look-at target
+                    let focus_distance = if value.contains(", ") {
+                        let focus_str = values.next_str("a focus distance")?;
+                        let focus_distance: f32 =
+                            context.parse_value(focus_str, "a focus distance")?;
+                        if focus_distance <= 0. {
+                            return Err(context.parse_error(focus_str, "a positive focus distance"));
+                        }
+                        Some(focus_distance)
+                    } else {
+                        None
+                    };
+
+                    Ok((aperture_radius, focus_distance, context, value))
+                })?);
+            }
+            "projection" => {
+                projection = Some((
+                    Projection::try_from(value)
+                        .map_err(|error| context.error(value, ConfigErrorKind::Parse(error)))?,
+                    context,
+                    value,
+                ));
+            }
+            "spheres" => {
+                spheres = Some(multi_item_parse(value, context, |values| {
+                    Ok(Sphere::new(
+                        values.next_with(Point3::try_from)?,
+                        values.next("a radius")?,
+                        push_material_with_values(values, &mut materials)?,
+                    ))
+                })?);
+            }
+            "planes" => {
+                planes = Some(multi_item_parse(value, context, |values| {
+                    Ok(Plane::new(
+                        values.next_with(Point3::try_from)?,
+                        Vector3::normalize(values.next_with(Vector3::try_from)?),
+                        push_material_with_values(values, &mut materials)?,
+                    ))
+                })?);
+            }
+            "triangles" => {
                 let triangles = triangles.get_or_insert_with(Vec::new);
 
-                triangles.append(&mut multi_item_parse(value, |values| {
-                    Triangle::new(
-                        values.next().unwrap().into(),
-                        values.next().unwrap().into(),
-                        values.next().unwrap().into(),
+                triangles.append(&mut multi_item_parse(value, context, |values| {
+                    Ok(Triangle::new(
+                        values.next_with(Point3::try_from)?,
+                        values.next_with(Point3::try_from)?,
+                        values.next_with(Point3::try_from)?,
                         NormalsTextureCoordinates::None,
-                        push_material_with_values(values, &mut materials),
-                    )
-                }));
+                        push_material_with_values(values, &mut materials)?,
+                    ))
+                })?);
             }
-            ("obj", value) => {
+            "obj" => {
                 let triangles = triangles.get_or_insert_with(Vec::new);
 
-                for mut new_triangles in multi_item_parse(value, |value| {
+                for mut new_triangles in multi_item_parse(value, context, |values| {
+                    let name = values.next_str("an obj file name")?;
                     obj::parse(
-                        &format!("obj/{}.obj", value.next().unwrap()),
+                        &format!("obj/{name}.obj"),
                         &mut materials,
                         &mut texture_coordinates,
                         &mut normals,
                         &mut barycentric_precomputed,
+                        &mut file_hashes,
                     )
-                }) {
+                    .map_err(|error| context.error(name, ConfigErrorKind::Obj(error)))
+                })? {
                     triangles.append(&mut new_triangles);
                 }
             }
-            (other, value) => panic!("Unknown entry {other} with value {value}"),
+            _ => return Err(context.error(entry, ConfigErrorKind::UnknownEntry)),
         }
     }
 
+    let missing = |entry: &str| ConfigError {
+        line: string.lines().count() + 1,
+        column: 1,
+        entry: entry.to_owned(),
+        kind: ConfigErrorKind::MissingEntry,
+    };
+
     // wrap
-    let screen = screen.unwrap();
-    let mut spheres = spheres.unwrap().into_boxed_slice();
-    let mut planes = planes.unwrap().into_boxed_slice();
-    let mut triangles = triangles.unwrap().into_boxed_slice();
+    let mut screen = screen.ok_or_else(|| missing("screen"))?;
+    let mut camera = camera.ok_or_else(|| missing("camera"))?;
+
+    if let Some((aperture_radius, focus_distance, context, value)) = lens {
+        let focus_distance = focus_distance
+            .or_else(|| {
+                camera
+                    .look_at
+                    .as_ref()
+                    .map(|look_at| camera.position.vector_to(look_at.target).length())
+            })
+            .ok_or_else(|| {
+                context.parse_error(value, "a focus distance, as the camera has no look-at")
+            })?;
+
+        camera.lens = Some(Lens::new(aperture_radius, focus_distance));
+    }
+    if let Some((projection, context, value)) = projection {
+        if camera.lens.is_some() && projection != Projection::Perspective {
+            return Err(context.parse_error(value, "perspective, as the camera has a lens"));
+        }
+        camera.projection = projection;
+    }
+
+    if let Some((context, value)) = screen_context
+        && camera.look_at.is_none()
+    {
+        return Err(context.parse_error(value, "screen corners, as the camera has no look-at"));
+    }
+    screen.frame(&camera);
+    let mut spheres = spheres
+        .ok_or_else(|| missing("spheres"))?
+        .into_boxed_slice();
+    let mut planes = planes.ok_or_else(|| missing("planes"))?.into_boxed_slice();
+    let mut triangles = triangles
+        .ok_or_else(|| missing("triangles"))?
+        .into_boxed_slice();
     let normals = normals.into_boxed_slice();
     let texture_coordinates = texture_coordinates.into_boxed_slice();
     let barycentric_precomputed = barycentric_precomputed.into_boxed_slice();
 
-    if let Some(amount) = incremental {
-        assert!(amount != 0);
-        assert!(screen.samples_per_pixel.is_multiple_of(amount));
+    // adaptive sampling renders up to its maximum, in passes of its minimum unless incremental is set
+    if let Some(adaptive) = adaptive {
+        screen.samples_per_pixel = adaptive.max_samples();
+    }
+    if let Some((amount, context, value)) = incremental
+        && !screen.samples_per_pixel.is_multiple_of(amount)
+    {
+        return Err(context.parse_error(value, "a divisor of the samples per pixel"));
+    }
+
+    // the noise is measured between passes
+    if let Some((context, value)) = noise_target_context
+        && incremental.is_none()
+        && adaptive.is_none()
+    {
+        return Err(context.parse_error(
+            value,
+            "an incremental or adaptive render, as the noise is measured after every pass",
+        ));
+    }
+
+    // the denoiser is guided by them
+    if denoiser.is_some() {
+        for aov in [Aov::Albedo, Aov::Normal] {
+            if !aovs.contains(&aov) {
+                aovs.push(aov);
+            }
+        }
     }
 
-    Scene::new(
-        incremental,
+    let bvhs = Bvhs::new(
+        BvhNode::new(&mut spheres).into_boxed_slice(),
+        BvhNode::new(&mut planes).into_boxed_slice(),
+        BvhNode::new(&mut triangles).into_boxed_slice(),
+    );
+    let lights = Light::collect(&spheres, &triangles, &materials.0);
+
+    Ok(Scene::new(
+        incremental
+            .map(|(amount, ..)| amount)
+            .or_else(|| adaptive.map(|adaptive| adaptive.min_samples())),
         continue_sampling,
-        screen,
-        camera.unwrap(),
-        Bvhs::new(
-            BvhNode::new(&mut spheres).into_boxed_slice(),
-            BvhNode::new(&mut planes).into_boxed_slice(),
-            BvhNode::new(&mut triangles).into_boxed_slice(),
+        // the scene description, with the contents of the files it names
+        rng::hash(
+            string
+                .bytes()
+                .map(u64::from)
+                .chain(file_hashes)
+                .chain(materials.0.iter().map(Material::texture_hash)),
         ),
+        seed,
+        sampler,
+        adaptive,
+        termination,
+        filter,
+        integrator,
+        russian_roulette,
+        aovs.into_boxed_slice(),
+        denoiser,
+        screen,
+        camera,
+        bvhs,
         Shapes::new(
             spheres,
             planes,
@@ -139,25 +528,34 @@ pub fn parse(string: &str) -> Scene {
             normals,
             texture_coordinates,
             barycentric_precomputed,
+            lights,
         ),
         materials.0.into_boxed_slice(),
-    )
+    ))
 }
 
 fn push_material_with_values(
-    values: &mut Split<&str>,
+    values: &mut Values,
     materials: &mut Interner<Material>,
-) -> MaterialIndexer {
-    materials.intern(Material::new(
-        values.next().unwrap().into(),
-        ColorKind::Solid(values.next().unwrap().into()),
-    ))
+) -> Result<MaterialIndexer, ConfigError> {
+    Ok(materials.intern(Material::new(
+        values.next_with(MaterialKind::try_from)?,
+        values.next_with(ColorKind::try_from)?,
+    )))
 }
 
 pub struct Interner<T: HasIndexer + PartialEq>(Vec<T>)
 where
     usize: Convert<T::IndexerType>;
 
+impl<T: HasIndexer + PartialEq> Default for Interner<T>
+where
+    usize: Convert<T::IndexerType>,
+{
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
 impl<T: HasIndexer + PartialEq> Interner<T>
 where
     usize: Convert<T::IndexerType>,
@@ -177,26 +575,110 @@ where
     }
 }
 
-fn single_item_parse<T>(value: &str, mut f: impl FnMut(&mut Split<&str>) -> T) -> T {
-    let mut values = value.split(", "); // Skip closing parenthesis with len - 1
+fn single_item_parse<'a, T>(
+    value: &'a str,
+    context: Context<'a>,
+    mut f: impl FnMut(&mut Values<'a>) -> Result<T, ConfigError>,
+) -> Result<T, ConfigError> {
+    let mut values = Values {
+        split: value.split(", "),
+        all: value,
+        context,
+    };
 
-    let parsed = f(&mut values);
+    let parsed = f(&mut values)?;
 
-    assert!(values.next().is_none());
+    if let Some(extra) = values.split.next() {
+        return Err(context.parse_error(extra, "no further values"));
+    }
 
-    parsed
+    Ok(parsed)
 }
 
-fn multi_item_parse<T>(str: &str, mut f: impl FnMut(&mut Split<&str>) -> T) -> Vec<T> {
+fn multi_item_parse<'a, T>(
+    str: &'a str,
+    context: Context<'a>,
+    mut f: impl FnMut(&mut Values<'a>) -> Result<T, ConfigError>,
+) -> Result<Vec<T>, ConfigError> {
     let mut parsed = Vec::new();
 
-    if str.len() > 1 {
-        let values = str[1..str.len() - 1].split("), ("); // Skip opening and closing parentheses with 1..len - 1
+    if !str.is_empty() {
+        // Skip opening and closing parentheses
+        let Some(items) = str.strip_prefix('(').and_then(|str| str.strip_suffix(')')) else {
+            return Err(context.parse_error(str, "a list of items of the form `(values), ...`"));
+        };
+
+        for value in items.split("), (") {
+            parsed.push(single_item_parse(value, context, &mut f)?);
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::SCENE;
+
+    /// The error of `scene`, with its line, column and entry
+    fn error(scene: &str) -> (usize, usize, String, ConfigErrorKind) {
+        let Err(error) = parse(scene) else {
+            panic!("expected an error");
+        };
+        (error.line, error.column, error.entry, error.kind)
+    }
+
+    #[test]
+    fn valid() {
+        parse(SCENE).unwrap();
+    }
+
+    #[test]
+    fn unknown_entry() {
+        let (line, column, entry, kind) = error(&format!("{SCENE}\n\nlights()"));
+        assert_eq!((line, column, entry.as_str()), (7, 1, "lights"));
+        assert!(matches!(kind, ConfigErrorKind::UnknownEntry));
+    }
+
+    #[test]
+    fn missing_entry() {
+        let scene = SCENE.replace("camera(0 0 -5)\n", "");
+        let (line, column, entry, kind) = error(&scene);
+        assert_eq!((line, column, entry.as_str()), (5, 1, "camera"));
+        assert!(matches!(kind, ConfigErrorKind::MissingEntry));
+    }
 
-        for value in values {
-            parsed.push(single_item_parse(value, &mut f));
+    #[test]
+    fn malformed_entry() {
+        let (line, column, _, kind) = error(&SCENE.replace("planes()", "planes"));
+        assert_eq!((line, column), (4, 1));
+        assert!(matches!(kind, ConfigErrorKind::Parse(_)));
+    }
+
+    #[test]
+    fn invalid_values() {
+        for (scene, (line, column, found)) in [
+            (SCENE.replace("0 0 0, 1,", "0 0 0, x,"), (3, 17, "x")),
+            (SCENE.replace("lambertian", "plastic"), (3, 20, "plastic")),
+            (SCENE.replace(", 8, 2)", ", 8)"), (1, 41, "")),
+            (SCENE.replace(", 8, 2)", ", 8, 2, 1)"), (1, 46, "1")),
+            (format!("{SCENE}\nincremental(3)"), (6, 13, "3")),
+            (format!("{SCENE}\nnoise_target(0.01)"), (6, 14, "0.01")),
+        ] {
+            let (error_line, error_column, _, kind) = error(&scene);
+            assert_eq!((error_line, error_column), (line, column), "{scene}");
+            let ConfigErrorKind::Parse(error) = kind else {
+                panic!("expected a parse error in {scene}");
+            };
+            assert_eq!(error.found, found, "{scene}");
         }
     }
 
-    parsed
+    #[test]
+    fn missing_obj() {
+        let (line, column, _, kind) = error(&format!("{SCENE}\nobj((missing))"));
+        assert_eq!((line, column), (6, 6));
+        assert!(matches!(kind, ConfigErrorKind::Obj(_)));
+    }
 }
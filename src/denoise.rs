@@ -0,0 +1,166 @@
+use std::mem;
+
+use crate::{
+    config::ParseError,
+    vec3::{Color, New as _},
+};
+
+/// The B3 spline, separably weighting the 5x5 taps of every iteration
+const KERNEL: [f32; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+
+/// An edge-avoiding à-trous wavelet filter (Dammertz et al. 2010), guided by the albedo and normal aovs.
+///
+/// It blurs the lighting with the albedo divided out, so textures stay sharp,
+/// and stops at edges in the color, the normals and the albedo, each weighted by its sigma.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Denoiser {
+    /// The amount of passes, each spreading its taps twice as far as the last
+    iterations: u32,
+    color_sigma: f32,
+    normal_sigma: f32,
+    albedo_sigma: f32,
+}
+impl Denoiser {
+    /// Filters `color` of an image with `width` columns, all buffers in the same pixel order
+    pub fn denoise(
+        &self,
+        width: usize,
+        color: &[Color<3, f32>],
+        albedo: &[Color<3, f32>],
+        normal: &[Color<3, f32>],
+    ) -> Vec<Color<3, f32>> {
+        let height = color.len().checked_div(width).unwrap_or(0);
+
+        // keeps black albedo from dividing by zero
+        let albedo_floor =
+            |albedo: Color<3, f32>| Color::new(albedo.into_inner().map(|e| e.max(1e-3)));
+
+        let mut irradiance = color
+            .iter()
+            .zip(albedo)
+            .map(|(&color, &albedo)| color / albedo_floor(albedo))
+            .collect::<Vec<_>>();
+        let mut filtered = irradiance.clone();
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            // the noise shrinks with every iteration
+            let color_sigma = self.color_sigma / f32::from(1_u16 << iteration);
+
+            for y in 0..height {
+                for x in 0..width {
+                    filtered[y * width + x] = self.filter_pixel(
+                        [x, y],
+                        [width, height],
+                        step,
+                        color_sigma,
+                        &irradiance,
+                        albedo,
+                        normal,
+                    );
+                }
+            }
+
+            mem::swap(&mut irradiance, &mut filtered);
+        }
+
+        irradiance
+            .into_iter()
+            .zip(albedo)
+            .map(|(irradiance, &albedo)| irradiance * albedo_floor(albedo))
+            .collect()
+    }
+
+    /// The weighted average of the taps around the pixel, `step` pixels apart
+    #[expect(clippy::too_many_arguments)]
+    fn filter_pixel(
+        &self,
+        [x, y]: [usize; 2],
+        [width, height]: [usize; 2],
+        step: usize,
+        color_sigma: f32,
+        irradiance: &[Color<3, f32>],
+        albedo: &[Color<3, f32>],
+        normal: &[Color<3, f32>],
+    ) -> Color<3, f32> {
+        let center = y * width + x;
+        let edge_stopping = |values: &[Color<3, f32>], tap: usize, sigma: f32| {
+            let difference = values[center] - values[tap];
+            (-difference.dot(difference) / (sigma * sigma)).exp()
+        };
+
+        let mut sum = Color::new([0.; 3]);
+        let mut weight_sum = 0.;
+
+        for (dy, y_weight) in KERNEL.into_iter().enumerate() {
+            for (dx, x_weight) in KERNEL.into_iter().enumerate() {
+                // the taps outside of the image are skipped
+                let (Some(tap_x), Some(tap_y)) = (
+                    (x + dx * step)
+                        .checked_sub(2 * step)
+                        .filter(|&tap_x| tap_x < width),
+                    (y + dy * step)
+                        .checked_sub(2 * step)
+                        .filter(|&tap_y| tap_y < height),
+                ) else {
+                    continue;
+                };
+                let tap = tap_y * width + tap_x;
+
+                let weight = x_weight
+                    * y_weight
+                    * edge_stopping(irradiance, tap, color_sigma)
+                    * edge_stopping(normal, tap, self.normal_sigma)
+                    * edge_stopping(albedo, tap, self.albedo_sigma);
+
+                sum = sum + irradiance[tap] * weight;
+                weight_sum += weight;
+            }
+        }
+
+        // the center tap always has a weight
+        sum / weight_sum
+    }
+}
+impl TryFrom<&str> for Denoiser {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        const EXPECTED: &str =
+            "optionally the iterations, followed by the color, normal and albedo sigmas";
+
+        let mut split = value.split_whitespace();
+
+        let iterations = split.next().map_or(Ok(5), |iterations| {
+            iterations
+                .parse()
+                .ok()
+                .filter(|iterations| (1..=15).contains(iterations))
+                .ok_or_else(|| ParseError::new("1 to 15 iterations", value))
+        })?;
+
+        // optional, positive parameters
+        let mut sigma = |default| {
+            split.next().map_or(Ok(default), |sigma| {
+                sigma
+                    .parse()
+                    .ok()
+                    .filter(|&sigma: &f32| sigma > 0.)
+                    .ok_or_else(|| ParseError::new(EXPECTED, value))
+            })
+        };
+
+        let denoiser = Self {
+            iterations,
+            color_sigma: sigma(1.)?,
+            normal_sigma: sigma(0.2)?,
+            albedo_sigma: sigma(0.1)?,
+        };
+
+        // no trailing parameters
+        match split.next() {
+            None => Ok(denoiser),
+            Some(_) => Err(ParseError::new("no further denoise parameters", value)),
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::{array, f32::consts::PI};
+
+use crate::config::ParseError;
+
+/// The amount of bins `Filter` tabulates per axis
+const TABLE_SIZE: usize = 64;
+
+/// Reconstructs the pixels from their samples, by distributing the samples proportionally to |f|.
+///
+/// The weight of every sample is then just the sign of f, so negative lobes can't blow up the weighted average.
+#[derive(Debug, PartialEq)]
+pub struct Filter {
+    kind: FilterKind,
+    /// The normalized running sum of |f| over the bins of 0..radius
+    cdf: [f32; TABLE_SIZE],
+    /// If f is negative in the bin
+    negative: [bool; TABLE_SIZE],
+}
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new(FilterKind::Box { radius: 0.5 })
+    }
+}
+impl Filter {
+    #[expect(clippy::cast_precision_loss)]
+    pub fn new(kind: FilterKind) -> Self {
+        let radius = kind.radius();
+        let values: [f32; TABLE_SIZE] =
+            array::from_fn(|bin| kind.evaluate((bin as f32 + 0.5) / TABLE_SIZE as f32 * radius));
+
+        let mut sum = 0.;
+        let mut cdf = values.map(|value| {
+            sum += value.abs();
+            sum
+        });
+        for entry in &mut cdf {
+            *entry /= sum;
+        }
+
+        Self {
+            kind,
+            cdf,
+            negative: values.map(|value| value < 0.),
+        }
+    }
+
+    /// Maps a 2d sample to an offset in pixels from the pixel center and the weight of the sample
+    pub fn sample(&self, [u, v]: [f32; 2]) -> ([f32; 2], f32) {
+        match self.kind {
+            FilterKind::Box { radius } => ([u, v].map(|e| (2. * e - 1.) * radius), 1.),
+            FilterKind::Tent { radius } => (
+                [u, v].map(|e| {
+                    if e < 0.5 {
+                        radius * ((2. * e).sqrt() - 1.)
+                    } else {
+                        radius * (1. - (2. - 2. * e).sqrt())
+                    }
+                }),
+                1.,
+            ),
+            FilterKind::Gaussian { .. }
+            | FilterKind::Mitchell { .. }
+            | FilterKind::Lanczos { .. } => {
+                let [(x, x_negative), (y, y_negative)] = [u, v].map(|e| self.sample_tabulated(e));
+
+                ([x, y], if x_negative == y_negative { 1. } else { -1. })
+            }
+        }
+    }
+
+    /// Samples one axis proportionally to |f|, returning the offset and if f is negative there
+    #[expect(clippy::cast_precision_loss)]
+    fn sample_tabulated(&self, e: f32) -> (f32, bool) {
+        // the first half of the sample space picks the negative side
+        let (side, e) = if e < 0.5 {
+            (-1., 2. * e)
+        } else {
+            (1., 2. * e - 1.)
+        };
+
+        let bin = self
+            .cdf
+            .partition_point(|&cdf| cdf <= e)
+            .min(TABLE_SIZE - 1);
+        let start = bin.checked_sub(1).map_or(0., |previous| self.cdf[previous]);
+
+        // uniformly within the bin
+        let within = ((e - start) / (self.cdf[bin] - start)).clamp(0., 1.);
+        let offset = (bin as f32 + within) / TABLE_SIZE as f32 * self.kind.radius();
+
+        (side * offset, self.negative[bin])
+    }
+}
+
+/// The reconstruction filters, with `radius` in pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterKind {
+    /// Averages all samples in the pixel area
+    Box { radius: f32 },
+    /// Linearly falls off towards the radius
+    Tent { radius: f32 },
+    /// A gaussian with standard deviation `sigma`, shifted to reach zero at the radius
+    Gaussian { radius: f32, sigma: f32 },
+    /// The Mitchell-Netravali cubic, with its `b` and `c` parameters
+    Mitchell { radius: f32, b: f32, c: f32 },
+    /// A sinc windowed by a wider sinc, with `tau` sinc periods in the window
+    Lanczos { radius: f32, tau: f32 },
+}
+impl FilterKind {
+    const fn radius(self) -> f32 {
+        match self {
+            Self::Box { radius }
+            | Self::Tent { radius }
+            | Self::Gaussian { radius, .. }
+            | Self::Mitchell { radius, .. }
+            | Self::Lanczos { radius, .. } => radius,
+        }
+    }
+
+    /// The separable one-dimensional filter at `x` pixels from the center
+    fn evaluate(self, x: f32) -> f32 {
+        let x = x.abs();
+
+        match self {
+            Self::Box { radius } => {
+                if x <= radius {
+                    1.
+                } else {
+                    0.
+                }
+            }
+            Self::Tent { radius } => (radius - x).max(0.),
+            Self::Gaussian { radius, sigma } => {
+                let gaussian = |x: f32| (-x * x / (2. * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(radius)).max(0.)
+            }
+            Self::Mitchell { radius, b, c } => {
+                // the cubic is defined over 0..2
+                let x = 2. * x / radius;
+
+                let value = if x < 1. {
+                    (12. - 9. * b - 6. * c) * x.powi(3)
+                        + (-18. + 12. * b + 6. * c) * x * x
+                        + (6. - 2. * b)
+                } else if x < 2. {
+                    (-b - 6. * c) * x.powi(3)
+                        + (6. * b + 30. * c) * x * x
+                        + (-12. * b - 48. * c) * x
+                        + (8. * b + 24. * c)
+                } else {
+                    0.
+                };
+
+                value / 6.
+            }
+            Self::Lanczos { radius, tau } => {
+                if x < radius {
+                    sinc(x) * sinc(x / tau)
+                } else {
+                    0.
+                }
+            }
+        }
+    }
+}
+impl TryFrom<&str> for FilterKind {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut split = value.split_whitespace();
+        let kind = split.next();
+
+        // optional, non-negative parameters
+        let mut parameter = |default, expected| {
+            split.next().map_or(Ok(default), |parameter| {
+                parameter
+                    .parse()
+                    .ok()
+                    .filter(|&parameter: &f32| parameter >= 0.)
+                    .ok_or_else(|| ParseError::new(expected, value))
+            })
+        };
+
+        let filter = match kind {
+            Some("box") => Self::Box {
+                radius: parameter(0.5, "box optionally followed by its radius")?,
+            },
+            Some("tent") => Self::Tent {
+                radius: parameter(1., "tent optionally followed by its radius")?,
+            },
+            Some("gaussian") => Self::Gaussian {
+                radius: parameter(1.5, "gaussian optionally followed by its radius and sigma")?,
+                sigma: parameter(0.5, "gaussian optionally followed by its radius and sigma")?,
+            },
+            Some("mitchell") => Self::Mitchell {
+                radius: parameter(2., "mitchell optionally followed by its radius, b and c")?,
+                b: parameter(
+                    1. / 3.,
+                    "mitchell optionally followed by its radius, b and c",
+                )?,
+                c: parameter(
+                    1. / 3.,
+                    "mitchell optionally followed by its radius, b and c",
+                )?,
+            },
+            Some("lanczos") => Self::Lanczos {
+                radius: parameter(2., "lanczos optionally followed by its radius and tau")?,
+                tau: parameter(2., "lanczos optionally followed by its radius and tau")?,
+            },
+            _ => {
+                return Err(ParseError::new(
+                    "one of box, tent, gaussian, mitchell or lanczos",
+                    value,
+                ));
+            }
+        };
+
+        // only the mitchell b and c may be zero
+        let positive = match filter {
+            Self::Box { radius } | Self::Tent { radius } | Self::Mitchell { radius, .. } => {
+                radius > 0.
+            }
+            Self::Gaussian { radius, sigma } => radius > 0. && sigma > 0.,
+            Self::Lanczos { radius, tau } => radius > 0. && tau > 0.,
+        };
+        if !positive {
+            return Err(ParseError::new("positive filter widths", value));
+        }
+
+        // no trailing parameters
+        match split.next() {
+            None => Ok(filter),
+            Some(_) => Err(ParseError::new("no further filter parameters", value)),
+        }
+    }
+}
+
+/// The normalized sinc function
+fn sinc(x: f32) -> f32 {
+    if x < 1e-5 {
+        1.
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            FilterKind::try_from("gaussian"),
+            Ok(FilterKind::Gaussian {
+                radius: 1.5,
+                sigma: 0.5
+            })
+        );
+        assert_eq!(
+            FilterKind::try_from("mitchell 2 0"),
+            Ok(FilterKind::Mitchell {
+                radius: 2.,
+                b: 0.,
+                c: 1. / 3.
+            })
+        );
+        for invalid in [
+            "",
+            "sinc",
+            "box -1",
+            "box x",
+            "tent 0",
+            "gaussian 1 0",
+            "box 1 2",
+        ] {
+            assert!(FilterKind::try_from(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    /// The samples of a grid over the 2d sample space
+    #[expect(clippy::cast_precision_loss)]
+    #[expect(clippy::integer_division)]
+    fn samples(filter: &Filter) -> impl Iterator<Item = ([f32; 2], f32)> {
+        (0..128 * 128).map(|i| {
+            let [u, v] = [i % 128, i / 128].map(|e| (e as f32 + 0.5) / 128.);
+            filter.sample([u, v])
+        })
+    }
+
+    #[test]
+    fn samples_stay_within_the_radius() {
+        for kind in ["box 0.7", "tent", "gaussian", "mitchell", "lanczos 3"] {
+            let kind = FilterKind::try_from(kind).unwrap();
+            let radius = kind.radius();
+
+            for ([x, y], _) in samples(&Filter::new(kind)) {
+                assert!(x.abs() <= radius && y.abs() <= radius, "{kind:?}: {x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn negative_lobes_weigh_negatively() {
+        for kind in ["mitchell", "lanczos 3"] {
+            let kind = FilterKind::try_from(kind).unwrap();
+            let filter = Filter::new(kind);
+
+            // the integral of f relative to the one of |f|, per axis
+            let [integral, absolute] = (0..10_000).fold([0.; 2], |[integral, absolute], i| {
+                #[expect(clippy::cast_precision_loss)]
+                let value = kind.evaluate((i as f32 + 0.5) / 10_000. * kind.radius());
+                [integral + value, absolute + value.abs()]
+            });
+            let expected = (integral / absolute).powi(2);
+
+            #[expect(clippy::cast_precision_loss)]
+            let mean = samples(&filter).map(|(_, weight)| weight).sum::<f32>() / (128 * 128) as f32;
+            assert!(
+                (mean - expected).abs() < 0.01,
+                "{kind:?}: {mean} {expected}"
+            );
+            assert!(mean < 1.);
+        }
+    }
+}
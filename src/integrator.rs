@@ -0,0 +1,640 @@
+use std::{
+    fmt::Debug,
+    str::{FromStr, SplitWhitespace},
+};
+
+use crate::{
+    Ray, Scene, ShapeIndex,
+    config::ParseError,
+    material::{Material, Scatter},
+    media::Media,
+    power_heuristic, rng, sampler,
+    shapes::MaterialIndexer,
+    spectrum::{ColorModel, Rgb, Wavelengths},
+    vec3::{Color, New as _, NormalizedVector3, Point3},
+};
+
+/// A light transport algorithm, computing the color of the camera rays.
+/// New ones are added to `parse`, which hands them their options from the scene description.
+pub trait Integrator: Debug + Send + Sync {
+    /// The color arriving at the camera along `ray`, and the first surface it hit, which the aovs show
+    #[expect(clippy::type_complexity)]
+    fn ray_color(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        bvh_stack: &mut Vec<(f32, u32)>, // is reused across shape types
+    ) -> (
+        Color<3, f32>,
+        Option<(
+            (f32, Point3, (NormalizedVector3, [f32; 2]), MaterialIndexer),
+            ShapeIndex,
+        )>,
+    );
+}
+
+/// Parses the name of an integrator, followed by its options
+/// # Errors
+/// When the integrator is unknown or its options are invalid
+pub fn parse(value: &str) -> Result<Box<dyn Integrator>, ParseError> {
+    let value = value.trim();
+    let (name, options) = value.split_once(' ').unwrap_or((value, ""));
+
+    match name {
+        "path" => Ok(Box::new(Path::try_from(options)?)),
+        "ao" => Ok(Box::new(AmbientOcclusion::try_from(options)?)),
+        "debug" => Ok(Box::new(DebugMode::try_from(options)?)),
+        _ => Err(ParseError::new(
+            "one of path, ao or debug, followed by its options",
+            value,
+        )),
+    }
+}
+
+/// Follows a random walk through the scene, sampling the lights at every bounce
+#[derive(Debug, Default)]
+pub struct Path {
+    /// Carries light at a few sampled wavelengths instead of rgb, so glasses with a varying refractive index disperse it
+    spectral: bool,
+}
+impl Integrator for Path {
+    fn ray_color(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        bvh_stack: &mut Vec<(f32, u32)>,
+    ) -> (
+        Color<3, f32>,
+        Option<(
+            (f32, Point3, (NormalizedVector3, [f32; 2]), MaterialIndexer),
+            ShapeIndex,
+        )>,
+    ) {
+        if self.spectral {
+            let mut wavelengths = Wavelengths::sample(sampler::get_1d());
+            let (radiance, first_hit) = Self::trace(scene, ray, bvh_stack, &mut wavelengths);
+            (wavelengths.to_rgb(radiance), first_hit)
+        } else {
+            Self::trace(scene, ray, bvh_stack, &mut Rgb)
+        }
+    }
+}
+impl Path {
+    /// The light arriving along `ray`, as carried by the color model, and the first surface it hit
+    #[expect(clippy::type_complexity, clippy::too_many_lines)]
+    fn trace<const N: usize>(
+        scene: &Scene,
+        ray: Ray,
+        bvh_stack: &mut Vec<(f32, u32)>,
+        model: &mut impl ColorModel<N>,
+    ) -> (
+        Color<N, f32>,
+        Option<(
+            (f32, Point3, (NormalizedVector3, [f32; 2]), MaterialIndexer),
+            ShapeIndex,
+        )>,
+    ) {
+        let mut current_ray = ray;
+        // the color the path has been multiplied with so far
+        let mut throughput = Color::new([1.; N]);
+        let mut radiance = Color::new([0.; N]);
+        // the pdf the last bounce scattered with, if it also sampled the lights directly
+        let mut scatter_pdf = None;
+        // the glasses the path is inside
+        let mut media = Media::default();
+        let mut first_hit = None;
+
+        for bounce in 0..scene.screen.max_bounces {
+            match closest_surface(
+                scene,
+                &current_ray,
+                (&mut media, &*model),
+                &mut throughput,
+                bvh_stack,
+            ) {
+                // skybox
+                None => {
+                    let a = 0.5 * (current_ray.direction.y() + 1.0); // y scaled to 0.5-1
+                    let sky = Color::new([0.2, 0.2, 0.8]) * (1.0 - a) + Color::new([1.; 3]) * a;
+
+                    radiance = radiance + throughput * model.upsample(sky);
+
+                    break;
+                }
+                // scattter
+                Some(
+                    hit @ (
+                        (distance, hit_point, (normal, texture_coordinates), shape_material_index),
+                        shape,
+                    ),
+                ) => {
+                    first_hit.get_or_insert(hit);
+                    let shape_material: &Material = shape_material_index.index(&*scene.materials);
+                    let medium = shape_material.medium();
+                    let entering = |ray: &Ray| ray.direction.dot(normal) < 0.;
+                    let outside_refractive_index = medium.map_or(1., |medium| {
+                        let outside =
+                            media.outside_refractive_index(medium, entering(&current_ray));
+                        // every wavelength refracts differently
+                        if medium.refractive_index.is_dispersive() || outside.is_dispersive() {
+                            model.terminate_secondary();
+                        }
+                        outside.at(model.wavelength())
+                    });
+
+                    match shape_material.scatter(
+                        &current_ray,
+                        normal,
+                        hit_point,
+                        texture_coordinates,
+                        outside_refractive_index,
+                        model.wavelength(),
+                    ) {
+                        Scatter::Scattered(ray, weight, pdf) => {
+                            // only directions with a density can be hit by light sampling
+                            if pdf.is_some()
+                                && let Some(light) = direct_light(
+                                    scene,
+                                    &current_ray,
+                                    (shape_material, outside_refractive_index),
+                                    (&media, &*model),
+                                    (normal, texture_coordinates),
+                                    hit_point,
+                                    bvh_stack,
+                                )
+                            {
+                                radiance = radiance + throughput * light;
+                            }
+
+                            // entering or leaving a glass
+                            if let Some(medium) = medium
+                                && entering(&current_ray) == entering(&ray)
+                            {
+                                media.cross(medium, entering(&ray));
+                            }
+
+                            scatter_pdf = pdf;
+                            throughput = throughput * model.upsample(weight);
+                            current_ray = ray;
+
+                            // continue with a probability of the throughput, which the survivors make up for
+                            if scene
+                                .russian_roulette
+                                .is_some_and(|min_bounces| bounce + 1 >= min_bounces)
+                            {
+                                let probability = throughput
+                                    .into_inner()
+                                    .into_iter()
+                                    .fold(0., f32::max)
+                                    .min(1.);
+                                if sampler::get_1d() >= probability {
+                                    break;
+                                }
+                                throughput = throughput / probability;
+                            }
+                        }
+                        Scatter::Absorbed => break,
+                        Scatter::Light(color) => {
+                            // light sampling could have found this light as well
+                            let weight = scatter_pdf.zip(shape.light()).map_or(
+                                1.,
+                                |(scatter_pdf, light)| {
+                                    power_heuristic(
+                                        scatter_pdf,
+                                        scene.light_pdf(light, &current_ray, distance),
+                                    )
+                                },
+                            );
+
+                            radiance = radiance + throughput * model.upsample(color) * weight;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        (radiance, first_hit)
+    }
+}
+
+/// The closest intersection along `ray`, passing through the surfaces of glasses inside glasses with a higher priority.
+/// Its distance is from the origin of `ray`, and `throughput` is absorbed by the media along the way.
+#[expect(clippy::type_complexity)]
+fn closest_surface<const N: usize>(
+    scene: &Scene,
+    ray: &Ray,
+    (media, model): (&mut Media, &impl ColorModel<N>),
+    throughput: &mut Color<N, f32>,
+    bvh_stack: &mut Vec<(f32, u32)>,
+) -> Option<(
+    (f32, Point3, (NormalizedVector3, [f32; 2]), MaterialIndexer),
+    ShapeIndex,
+)> {
+    let mut current_ray = Ray::new(ray.origin, ray.direction);
+    let mut skipped_distance = 0.;
+
+    loop {
+        let ((distance, hit_point, (normal, texture_coordinates), material_index), shape) =
+            scene.closest_intersection(&current_ray, bvh_stack)?;
+
+        // Beer-Lambert, along the way through the medium
+        if let Some(absorption) = media.current().and_then(|medium| medium.absorption) {
+            *throughput = *throughput * transmittance(model.upsample(absorption), distance);
+        }
+
+        let entering = current_ray.direction.dot(normal) < 0.;
+        match material_index.index(&*scene.materials).medium() {
+            Some(medium) if media.is_skipped(medium, entering) => {
+                media.cross(medium, entering);
+
+                skipped_distance += distance + 1e-4;
+                current_ray = Ray::new(
+                    hit_point + current_ray.direction.to_vector() * 1e-4,
+                    current_ray.direction,
+                );
+            }
+            _ => {
+                return Some((
+                    (
+                        skipped_distance + distance,
+                        hit_point,
+                        (normal, texture_coordinates),
+                        material_index,
+                    ),
+                    shape,
+                ));
+            }
+        }
+    }
+}
+
+/// Samples the incoming light at `hit_point` from one random light, weighted by `material` at its texture coordinates.
+/// Combined with the material's own scattering by multiple importance sampling.
+///
+/// The shadow ray passes through the same media as the path would, absorbed along the way.
+/// `outside_refractive_index` is the one on the other side of the surface.
+/// Returns the light arriving at the camera per unit throughput, or None if no light arrives.
+fn direct_light<const N: usize>(
+    scene: &Scene,
+    ray: &Ray,
+    (material, outside_refractive_index): (&Material, f32),
+    (media, model): (&Media, &impl ColorModel<N>),
+    (normal, texture_coordinates): (NormalizedVector3, [f32; 2]),
+    hit_point: Point3,
+    bvh_stack: &mut Vec<(f32, u32)>,
+) -> Option<Color<N, f32>> {
+    let lights = &scene.shapes.lights;
+
+    // always draw the samples, so the following dimensions stay aligned
+    let choice = sampler::get_1d();
+    let sample = sampler::get_2d();
+
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    #[expect(clippy::cast_precision_loss)]
+    let light = lights
+        .get(((choice * lights.len() as f32) as usize).min(lights.len().saturating_sub(1)))?;
+
+    // the side the ray came from
+    let facing_normal = if ray.direction.dot(normal) > 0. {
+        -normal
+    } else {
+        normal
+    };
+    let origin = hit_point + facing_normal.to_vector() * 1e-4;
+
+    let (direction, pdf) = light.sample_direction(&scene.shapes, origin, sample)?;
+    let (value, scatter_pdf) = material.evaluate(
+        ray,
+        normal,
+        direction,
+        texture_coordinates,
+        outside_refractive_index,
+        model.wavelength(),
+    )?;
+    if value.into_inner().iter().all(|&e| e <= 0.) {
+        return None;
+    }
+
+    // transmitted through the surface, into or out of its medium
+    let mut media = media.clone();
+    let origin = if facing_normal.dot(direction) > 0. {
+        origin
+    } else {
+        if let Some(medium) = material.medium() {
+            media.cross(medium, direction.dot(normal) < 0.);
+        }
+        hit_point - facing_normal.to_vector() * 1e-4
+    };
+
+    // the light has to be the closest surface in that direction
+    let shadow_ray = Ray::new(origin, direction);
+    let light_distance = light.intersects(&scene.shapes, &shadow_ray)?;
+    let mut transmittance = Color::new([1.; N]);
+    let ((distance, light_point, (light_normal, light_texture_coordinates), material_index), _) =
+        closest_surface(
+            scene,
+            &shadow_ray,
+            (&mut media, model),
+            &mut transmittance,
+            bvh_stack,
+        )?;
+    if distance < light_distance * (1. - 1e-4) {
+        return None;
+    }
+
+    // emitted from the point the shadow ray hit
+    let Scatter::Light(color) = material_index.index(&*scene.materials).scatter(
+        &shadow_ray,
+        light_normal,
+        light_point,
+        light_texture_coordinates,
+        1.,
+        model.wavelength(),
+    ) else {
+        return None;
+    };
+
+    #[expect(clippy::cast_precision_loss)]
+    let pdf = pdf / lights.len() as f32;
+
+    Some(
+        transmittance
+            * model.upsample(color)
+            * model.upsample(value * (power_heuristic(pdf, scatter_pdf) / pdf)),
+    )
+}
+
+/// The fraction of light that is not absorbed over `distance`
+fn transmittance<const N: usize>(absorption: Color<N, f32>, distance: f32) -> Color<N, f32> {
+    Color::new(
+        absorption
+            .into_inner()
+            .map(|absorption| (-absorption * distance).exp()),
+    )
+}
+
+impl TryFrom<&str> for Path {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim() {
+            "" => Ok(Self { spectral: false }),
+            "spectral" => Ok(Self { spectral: true }),
+            _ => Err(ParseError::new(
+                "path optionally followed by spectral",
+                value,
+            )),
+        }
+    }
+}
+
+/// Shades the first hit by how much of the hemisphere above it is open, up to `distance`
+#[derive(Debug)]
+pub struct AmbientOcclusion {
+    distance: f32,
+}
+impl Integrator for AmbientOcclusion {
+    fn ray_color(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        bvh_stack: &mut Vec<(f32, u32)>,
+    ) -> (
+        Color<3, f32>,
+        Option<(
+            (f32, Point3, (NormalizedVector3, [f32; 2]), MaterialIndexer),
+            ShapeIndex,
+        )>,
+    ) {
+        let Some(hit @ ((_, hit_point, (normal, _), _), _)) =
+            scene.closest_intersection(&ray, bvh_stack)
+        else {
+            return (Color::new([1.; 3]), None);
+        };
+
+        // the side the ray came from
+        let normal = if ray.direction.dot(normal) > 0. {
+            -normal
+        } else {
+            normal
+        };
+
+        // cosine distributed, so the open fraction is weighted like diffuse light
+        let direction = (normal + sampler::unit_sphere(sampler::get_2d())).normalize::<f32>();
+        let direction = if direction.to_vector().near_zero() {
+            normal
+        } else {
+            direction
+        };
+
+        let occlusion_ray = Ray::new(hit_point + normal.to_vector() * 1e-4, direction);
+        let occluded = scene
+            .closest_intersection(&occlusion_ray, bvh_stack)
+            .is_some_and(|((distance, ..), _)| distance < self.distance);
+
+        (Color::new([if occluded { 0. } else { 1. }; 3]), Some(hit))
+    }
+}
+impl TryFrom<&str> for AmbientOcclusion {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut split = value.split_whitespace();
+
+        let distance = split.next().map_or(Ok(1.), |distance| {
+            distance
+                .parse()
+                .ok()
+                .filter(|&distance: &f32| distance > 0.)
+                .ok_or_else(|| {
+                    ParseError::new("ao optionally followed by a positive distance", value)
+                })
+        })?;
+
+        // no trailing parameters
+        match split.next() {
+            None => Ok(Self { distance }),
+            Some(_) => Err(ParseError::new("no further ao parameters", value)),
+        }
+    }
+}
+
+/// Shows a property of the first hit, to find out which part of a mesh or scene is broken.
+/// Rays that leave the scene are black.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugMode {
+    /// The shading normal, mapped from -1..1 to 0..1
+    Normals,
+    /// The distance, fading from white up close to black at `max`
+    Depth { max: f32 },
+    /// The texture coordinates in red and green
+    TextureCoordinates,
+    /// A color per material, hashed from its index
+    MaterialId,
+    /// The barycentric coordinates of triangles
+    Barycentrics,
+    /// A heatmap of the visited bvh nodes, saturating at `max`
+    Nodes { max: u32 },
+    /// A heatmap of the shapes tested for intersection, saturating at `max`
+    Primitives { max: u32 },
+}
+impl Integrator for DebugMode {
+    fn ray_color(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        bvh_stack: &mut Vec<(f32, u32)>,
+    ) -> (
+        Color<3, f32>,
+        Option<(
+            (f32, Point3, (NormalizedVector3, [f32; 2]), MaterialIndexer),
+            ShapeIndex,
+        )>,
+    ) {
+        let mut visits = [0; 2];
+        let hit = scene.closest_intersection_counting(&ray, bvh_stack, &mut visits);
+
+        let color = match (*self, hit) {
+            // rays that miss still traverse the bvh
+            (Self::Nodes { max }, _) => heatmap(visits[0], max),
+            (Self::Primitives { max }, _) => heatmap(visits[1], max),
+            (Self::Barycentrics, Some(((_, hit_point, ..), ShapeIndex::Triangle(index)))) => {
+                Color::new(
+                    index
+                        .index(&*scene.shapes.triangles)
+                        .barycentrics(&hit_point),
+                )
+            }
+            // only triangles have barycentric coordinates
+            (_, None) | (Self::Barycentrics, _) => Color::new([0.; 3]),
+            (Self::Normals, Some(((_, _, (normal, _), _), _))) => {
+                Color::new(normal.into_inner().map(|e| 0.5 * (e + 1.)))
+            }
+            (Self::Depth { max }, Some(((distance, ..), _))) => {
+                Color::new([(1. - distance / max).max(0.); 3])
+            }
+            (Self::TextureCoordinates, Some(((_, _, (_, [u, v]), _), _))) => {
+                Color::new([u.rem_euclid(1.), v.rem_euclid(1.), 0.])
+            }
+            (Self::MaterialId, Some(((.., material_index), _))) => {
+                let hash = rng::hash([material_index.inner() as u64]);
+
+                #[expect(clippy::cast_possible_truncation)]
+                Color::new([0, 8, 16].map(|shift| f32::from((hash >> shift) as u8) / 255.))
+            }
+        };
+
+        (color, hit)
+    }
+}
+impl TryFrom<&str> for DebugMode {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        // optional, positive parameters
+        fn parameter<T: FromStr + PartialOrd>(
+            split: &mut SplitWhitespace<'_>,
+            default: T,
+            min: &T,
+            expected: &'static str,
+            value: &str,
+        ) -> Result<T, ParseError> {
+            split.next().map_or(Ok(default), |parameter| {
+                parameter
+                    .parse()
+                    .ok()
+                    .filter(|parameter| parameter >= min)
+                    .ok_or_else(|| ParseError::new(expected, value))
+            })
+        }
+
+        let mut split = value.split_whitespace();
+        let kind = split.next();
+
+        let mode = match kind {
+            Some("normals") => Self::Normals,
+            Some("depth") => Self::Depth {
+                max: parameter(
+                    &mut split,
+                    10.,
+                    &f32::MIN_POSITIVE,
+                    "depth optionally followed by its maximum distance",
+                    value,
+                )?,
+            },
+            Some("uv") => Self::TextureCoordinates,
+            Some("material") => Self::MaterialId,
+            Some("barycentrics") => Self::Barycentrics,
+            // counts, as a maximum below 1 would divide by zero
+            Some("nodes") => Self::Nodes {
+                max: parameter(
+                    &mut split,
+                    64,
+                    &1,
+                    "nodes optionally followed by its maximum count, a whole number of at least 1",
+                    value,
+                )?,
+            },
+            Some("primitives") => Self::Primitives {
+                max: parameter(
+                    &mut split,
+                    32,
+                    &1,
+                    "primitives optionally followed by its maximum count, a whole number of at least 1",
+                    value,
+                )?,
+            },
+            _ => {
+                return Err(ParseError::new(
+                    "one of normals, depth, uv, material, barycentrics, nodes or primitives",
+                    value,
+                ));
+            }
+        };
+
+        // no trailing parameters
+        match split.next() {
+            None => Ok(mode),
+            Some(_) => Err(ParseError::new("no further debug parameters", value)),
+        }
+    }
+}
+
+/// Maps `count` to black, red, yellow and white at `max`
+#[expect(clippy::cast_precision_loss)]
+fn heatmap(count: u32, max: u32) -> Color<3, f32> {
+    let heat = (count as f32 / max as f32).min(1.);
+
+    Color::new([0., 1., 2.].map(|offset| (3. * heat - offset).clamp(0., 1.)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_mode_parameters() {
+        assert_eq!(
+            DebugMode::try_from("nodes 8"),
+            Ok(DebugMode::Nodes { max: 8 })
+        );
+        assert_eq!(
+            DebugMode::try_from("primitives"),
+            Ok(DebugMode::Primitives { max: 32 })
+        );
+        assert_eq!(
+            DebugMode::try_from("depth 0.5"),
+            Ok(DebugMode::Depth { max: 0.5 })
+        );
+        for invalid in [
+            "nodes 0.5",
+            "nodes 0",
+            "primitives -1",
+            "depth 0",
+            "normals 1",
+        ] {
+            assert!(DebugMode::try_from(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -24,55 +24,157 @@
 #![allow(clippy::return_self_not_must_use)]
 
 pub mod aabb;
+pub mod adaptive;
+pub mod aov;
 pub mod bvh;
+pub mod checkpoint;
+pub mod cli;
 pub mod config;
 pub mod convert;
 pub mod cpu_affinity;
+pub mod denoise;
+pub mod filter;
 pub mod indices;
+pub mod integrator;
+pub mod light;
 pub mod material;
+pub mod media;
+pub mod microfacet;
 pub mod mmap;
 pub mod obj;
+pub mod principled;
 pub mod rng;
+pub mod sampler;
 pub mod shapes;
+pub mod spectrum;
+pub mod termination;
 pub mod transform;
 pub mod vec3;
 
+#[cfg(test)]
+mod testing;
+
 pub static SCENE: OnceLock<Scene> = OnceLock::new();
 
 use crate::{
-    shapes::{Plane, Sphere},
-    vec3::{BaseVector, Color, New as _, Point3},
+    config::ParseError,
+    indices::Indexer,
+    shapes::{MaterialIndexer, Plane, Sphere},
+    vec3::{Color, New as _, Point3},
 };
 use std::{
     array,
-    io::{Write as _, stdout},
+    f32::consts::{PI, TAU},
+    fs,
+    io::{self, Write as _, stdout},
+    iter,
+    num::NonZeroUsize,
+    ops::Range,
+    path::Path,
+    str::FromStr,
     sync::{
         Mutex, OnceLock,
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
     },
     thread::{self, available_parallelism},
+    time::Instant,
 };
 
+use adaptive::AdaptiveSampling;
+use aov::Aov;
 use bvh::BvhNode;
+use checkpoint::{Checkpoint, ResumeError};
 use cpu_affinity::set_cpu_affinity;
-use material::{Material, Scatter};
+use denoise::Denoiser;
+use filter::Filter;
+use integrator::Integrator;
+use light::Light;
+use material::Material;
 use mmap::{ColorChannel, MmapFile, Pixel};
-use rng::Random as _;
+use sampler::SamplerKind;
 use shapes::Triangle;
+use termination::{Termination, TimeLimit};
 use vec3::{NormalizedVector3, Vector3};
 
-/// A ppm p6 image
+/// Where and in which format the rendered image is written
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    pub path: String,
+    pub format: Format,
+}
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            path: "target/out.ppm".to_owned(),
+            format: Format::Ppm,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Binary ppm (p6), gamma corrected 8-bit colors
+    Ppm,
+    /// Portable float map, linear 32-bit float colors
+    Pfm,
+}
+impl Format {
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Ppm => "ppm",
+            Self::Pfm => "pfm",
+        }
+    }
+    /// Infers the format from the file extension, defaulting to ppm
+    pub fn from_path(path: &str) -> Self {
+        if Path::new(path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("pfm"))
+        {
+            Self::Pfm
+        } else {
+            Self::Ppm
+        }
+    }
+}
+impl FromStr for Format {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "ppm" => Ok(Self::Ppm),
+            "pfm" => Ok(Self::Pfm),
+            other => Err(ParseError::new("ppm or pfm", other)),
+        }
+    }
+}
+
+/// A memory mapped ppm p6 or pfm image
 pub struct Image {
     file: MmapFile,
     header_offset: usize,
 }
 impl Image {
-    fn new(width: usize, height: usize) -> Self {
-        let header = format!("P6\n{width} {height} {}\n", ColorChannel::MAX);
-        let mut file = MmapFile::new(
-            "target/out.ppm",
-            header.len() + width * height * size_of::<Pixel>(),
-        );
+    fn new(output: &Output, width: usize, height: usize) -> Self {
+        let (header, pixel_size) = match output.format {
+            Format::Ppm => (
+                format!("P6\n{width} {height} {}\n", ColorChannel::MAX),
+                size_of::<Pixel>(),
+            ),
+            Format::Pfm => {
+                // negative scale -> little endian
+                let mut header = format!("PF\n{width} {height}\n-1.0");
+                // pad the scale so the floats are aligned
+                while !(header.len() + 1).is_multiple_of(align_of::<f32>()) {
+                    header.push('0');
+                }
+                header.push('\n');
+
+                (header, size_of::<Color<3, f32>>())
+            }
+        };
+
+        let mut file = MmapFile::new(&output.path, header.len() + width * height * pixel_size);
 
         file.as_slice_mut().write_all(header.as_bytes()).unwrap();
 
@@ -81,13 +183,217 @@ impl Image {
             header_offset: header.len(),
         }
     }
-    fn data(&mut self) -> &mut [Pixel] {
+    fn data<P: ImagePixel>(&mut self) -> &mut [P] {
         // SAFETY:
         // All bit patterns are valid Pixels
         unsafe { self.file.as_casted_slice_mut(self.header_offset) }
     }
 }
 
+/// The linear radiance sums of every pixel, memory mapped next to the image.
+/// Continued and incremental renders add to them, so the image never accumulates quantization errors.
+pub struct Accumulation {
+    file: MmapFile,
+    /// If there was no accumulation file of this size before
+    created: bool,
+}
+impl Accumulation {
+    fn path(output: &Output) -> String {
+        format!("{}.accumulation", output.path)
+    }
+    fn new(output: &Output, width: usize, height: usize) -> Self {
+        let path = Self::path(output);
+        let len = width * height * size_of::<Accumulator>();
+        // one of another resolution or layout holds no usable sums
+        let created = fs::metadata(&path)
+            .ok()
+            .is_none_or(|metadata| metadata.len() != len as u64);
+
+        Self {
+            file: MmapFile::new(&path, len),
+            created,
+        }
+    }
+    fn data(&mut self) -> &mut [Accumulator] {
+        // SAFETY:
+        // All bit patterns are valid Accumulators
+        unsafe { self.file.as_casted_slice_mut(0) }
+    }
+}
+
+/// The running sums of a pixel, in image order
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Accumulator {
+    /// The filter-weighted linear radiance
+    radiance: [f64; 3],
+    weight: f64,
+    samples: u64,
+    /// The unweighted luminance of the samples and its square, estimating the noise of the pixel
+    luminance: f64,
+    luminance_squared: f64,
+    /// The samples in the luminance sums
+    luminance_samples: u64,
+}
+impl Accumulator {
+    /// As if `samples` samples with the summed filter `weight` had resulted in `color`.
+    /// Their noise is unknown, so it is estimated from the samples added later.
+    fn from_color(color: Color<3, f32>, samples: u64, weight: f64) -> Self {
+        Self {
+            radiance: color.into_inner().map(|e| f64::from(e) * weight),
+            weight,
+            samples,
+            ..Self::default()
+        }
+    }
+    /// Adds a sample of `color`, weighted by the filter
+    fn add_sample(&mut self, color: Color<3, f32>, weight: f32) {
+        for (sum, color) in self.radiance.iter_mut().zip(color.into_inner()) {
+            *sum += f64::from(color * weight);
+        }
+        self.weight += f64::from(weight);
+        self.samples += 1;
+
+        let luminance = f64::from(luminance(color));
+        self.luminance += luminance;
+        self.luminance_squared += luminance * luminance;
+        self.luminance_samples += 1;
+    }
+    fn add(&mut self, other: &Self) {
+        for (sum, radiance) in self.radiance.iter_mut().zip(other.radiance) {
+            *sum += radiance;
+        }
+        self.weight += other.weight;
+        self.samples += other.samples;
+        self.luminance += other.luminance;
+        self.luminance_squared += other.luminance_squared;
+        self.luminance_samples += other.luminance_samples;
+    }
+    /// The relative error of the mean luminance, its standard error divided by the mean.
+    /// The mean is floored at 0.01, so the error of darker pixels is absolute and black ones converge.
+    #[expect(clippy::cast_precision_loss)]
+    fn noise(&self) -> f64 {
+        if self.luminance_samples < 2 {
+            return f64::INFINITY;
+        }
+
+        let samples = self.luminance_samples as f64;
+        let mean = self.luminance / samples;
+        // the unbiased variance of the samples, divided by their count
+        let variance = (self.luminance_squared / samples - mean * mean).max(0.) / (samples - 1.);
+
+        variance.sqrt() / mean.max(0.01)
+    }
+    /// The weighted average, negative filter lobes can cancel out the weights
+    #[expect(clippy::cast_possible_truncation)]
+    fn color(&self) -> Color<3, f32> {
+        Color::new(if self.weight > 0. {
+            self.radiance.map(|e| (e / self.weight).max(0.) as f32)
+        } else {
+            [0.; 3]
+        })
+    }
+}
+
+/// The progress of the passes of a render, shared by its threads
+struct Passes {
+    /// The finished chunks of every pass
+    finished_chunks: Box<[AtomicUsize]>,
+    /// The summed noise of the pixels after every pass
+    noise: Box<[Mutex<f64>]>,
+    /// The amount of passes that are fully done, also keeping the checkpoints in order
+    finished: Mutex<usize>,
+    /// Set when the render terminates early, after which no further chunks are started
+    stopped: AtomicBool,
+}
+impl Passes {
+    fn new(passes: usize) -> Self {
+        Self {
+            finished_chunks: iter::repeat_with(|| AtomicUsize::new(0))
+                .take(passes)
+                .collect(),
+            noise: iter::repeat_with(|| Mutex::new(0.)).take(passes).collect(),
+            finished: Mutex::new(0),
+            stopped: AtomicBool::new(false),
+        }
+    }
+    /// Terminates the render early, reporting the reason once
+    fn stop(&self, reason: &str) {
+        if !self.stopped.swap(true, Ordering::Relaxed) {
+            println!("\n{reason}, stopping");
+        }
+    }
+}
+
+/// The perceived brightness of a linear color, with the Rec. 709 weights
+fn luminance(color: Color<3, f32>) -> f32 {
+    let [r, g, b] = color.into_inner();
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// A pixel as it is stored in an output image
+trait ImagePixel: Copy + Send {
+    /// Whether rows are stored from bottom to top
+    const BOTTOM_UP: bool;
+
+    fn from_color(color: Color<3, f32>) -> Self;
+    /// The linear color, as far as the pixel preserved it
+    fn to_color(self) -> Color<3, f32>;
+}
+impl ImagePixel for Pixel {
+    const BOTTOM_UP: bool = false;
+
+    fn from_color(color: Color<3, f32>) -> Self {
+        clamp(color).color_correct().to_natural_color::<u8>()
+    }
+    fn to_color(self) -> Color<3, f32> {
+        // undo the gamma correction
+        Color::new(self.to_float_color::<f32>().into_inner().map(|e| e * e))
+    }
+}
+/// Clamps to the displayable range, as filters can overshoot
+fn clamp(color: Color<3, f32>) -> Color<3, f32> {
+    Color::new(color.into_inner().map(|e| e.clamp(0., 1.)))
+}
+impl ImagePixel for Color<3, f32> {
+    const BOTTOM_UP: bool = true;
+
+    fn from_color(color: Color<3, f32>) -> Self {
+        color
+    }
+    fn to_color(self) -> Color<3, f32> {
+        self
+    }
+}
+
+/// The shape an intersection is on
+#[derive(Clone, Copy, Debug)]
+pub enum ShapeIndex {
+    Sphere(Indexer<u32, Sphere>),
+    Plane(Indexer<u32, Plane>),
+    Triangle(Indexer<u32, Triangle>),
+}
+impl ShapeIndex {
+    /// The shape as a light, if it is of a kind that can be sampled
+    const fn light(self) -> Option<Light> {
+        match self {
+            Self::Sphere(index) => Some(Light::Sphere(index)),
+            Self::Plane(_) => None,
+            Self::Triangle(index) => Some(Light::Triangle(index)),
+        }
+    }
+    /// Numbers all shapes of the scene, spheres first, then planes and triangles
+    fn id(self, shapes: &Shapes) -> usize {
+        match self {
+            Self::Sphere(index) => index.inner() as usize,
+            Self::Plane(index) => shapes.spheres.len() + index.inner() as usize,
+            Self::Triangle(index) => {
+                shapes.spheres.len() + shapes.planes.len() + index.inner() as usize
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Ray {
     origin: Point3,
@@ -101,8 +407,26 @@ impl Ray {
 
 #[derive(Debug)]
 pub struct Scene {
+    output: Output,
+    threads: Option<NonZeroUsize>,
     incremental: Option<usize>,
     continue_sampling: Option<usize>,
+    /// Keep the samples of the accumulation file and complete every pixel
+    resume: bool,
+    /// Identifies the scene description and the files it references in checkpoints
+    source_hash: u64,
+    /// Seeds the random numbers of every sample
+    seed: u64,
+    sampler: SamplerKind,
+    adaptive: Option<AdaptiveSampling>,
+    termination: Termination,
+    filter: Filter,
+    integrator: Box<dyn Integrator>,
+    /// The bounces after which paths are terminated randomly, based on their throughput
+    russian_roulette: Option<usize>,
+    aovs: Box<[Aov]>,
+    /// Writes a denoised copy of the image, which requires the albedo and normal aovs
+    denoiser: Option<Denoiser>,
     screen: Screen,
     camera: Camera,
     shapes: Shapes,
@@ -119,6 +443,8 @@ pub struct Shapes {
     texture_coordinates: Box<[[[f32; 2]; 3]]>,
     /// [d00, d01, d11, denominator]
     barycentric_precomputed: Box<[[f32; 4]]>,
+    /// The emissive spheres and triangles
+    lights: Box<[Light]>,
 }
 impl Shapes {
     const fn new(
@@ -128,6 +454,7 @@ impl Shapes {
         vertex_normals: Box<[[NormalizedVector3; 3]]>,
         texture_coordinates: Box<[[[f32; 2]; 3]]>,
         barycentric_precomputed: Box<[[f32; 4]]>,
+        lights: Box<[Light]>,
     ) -> Self {
         Self {
             spheres,
@@ -136,6 +463,7 @@ impl Shapes {
             vertex_normals,
             texture_coordinates,
             barycentric_precomputed,
+            lights,
         }
     }
 }
@@ -163,9 +491,20 @@ impl Bvhs {
 }
 
 impl Scene {
-    const fn new(
+    #[expect(clippy::too_many_arguments)]
+    fn new(
         incremental: Option<usize>,
         continue_sampling: Option<usize>,
+        source_hash: u64,
+        seed: u64,
+        sampler: SamplerKind,
+        adaptive: Option<AdaptiveSampling>,
+        termination: Termination,
+        filter: Filter,
+        integrator: Box<dyn Integrator>,
+        russian_roulette: Option<usize>,
+        aovs: Box<[Aov]>,
+        denoiser: Option<Denoiser>,
         screen: Screen,
         camera: Camera,
         bvhs: Bvhs,
@@ -173,8 +512,21 @@ impl Scene {
         materials: Box<[Material]>,
     ) -> Self {
         Self {
+            output: Output::default(),
+            threads: None,
             incremental,
             continue_sampling,
+            resume: false,
+            source_hash,
+            seed,
+            sampler,
+            adaptive,
+            termination,
+            filter,
+            integrator,
+            russian_roulette,
+            aovs,
+            denoiser,
             screen,
             camera,
             shapes,
@@ -183,23 +535,209 @@ impl Scene {
         }
     }
 
-    // The only precision loss is turning the resolution into floats, which is fine
-    #[expect(clippy::cast_precision_loss)]
+    /// Resumes the render from the checkpoint next to the output, returning the samples per pixel it had
+    /// # Errors
+    /// When there is no valid checkpoint or accumulation file, or the scene changed since
+    pub fn resume(&mut self) -> Result<u64, ResumeError> {
+        let checkpoint =
+            Checkpoint::read(&Checkpoint::path(&self.output)).map_err(ResumeError::Io)?;
+
+        if checkpoint.scene_hash != self.hash() || checkpoint.seed != self.seed {
+            return Err(ResumeError::SceneChanged);
+        }
+        if !Path::new(&Accumulation::path(&self.output)).exists() {
+            return Err(ResumeError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the accumulation file is missing",
+            )));
+        }
+
+        self.resume = true;
+        Ok(checkpoint.samples)
+    }
+
+    /// Identifies everything that changes the image, apart from the samples already taken
+    fn hash(&self) -> u64 {
+        rng::hash([
+            self.source_hash,
+            self.screen.resolution_width as u64,
+            self.screen.resolution_height as u64,
+            self.screen.samples_per_pixel as u64,
+            self.screen.max_bounces as u64,
+            self.sampler as u64,
+            u64::from(self.output.format == Format::Pfm),
+            // accumulation files of another layout can't be resumed
+            size_of::<Accumulator>() as u64,
+        ])
+    }
+
+    fn write_checkpoint(&self, samples: usize) {
+        let checkpoint = Checkpoint {
+            scene_hash: self.hash(),
+            seed: self.seed,
+            samples: samples as u64,
+        };
+
+        if let Err(error) = checkpoint.write(&Checkpoint::path(&self.output)) {
+            eprintln!("\nError writing the checkpoint: {error}");
+        }
+    }
+
     pub fn render(&self) {
-        let row_step = self.screen.top_edge / (self.screen.resolution_width - 1) as f32;
-        let column_step = self.screen.left_edge / (self.screen.resolution_height - 1) as f32;
+        let mut image = Image::new(
+            &self.output,
+            self.screen.resolution_width,
+            self.screen.resolution_height,
+        );
+        let mut accumulation = Accumulation::new(
+            &self.output,
+            self.screen.resolution_width,
+            self.screen.resolution_height,
+        );
+
+        let mut aov_images = self
+            .aovs
+            .iter()
+            .map(|aov| {
+                Image::new(
+                    &aov.output(&self.output),
+                    self.screen.resolution_width,
+                    self.screen.resolution_height,
+                )
+            })
+            .collect::<Vec<_>>();
+        let aovs = Mutex::new(
+            aov_images
+                .iter_mut()
+                .map(Image::data::<Color<3, f32>>)
+                .collect::<Vec<_>>(),
+        );
+
+        match self.output.format {
+            Format::Ppm => self.render_into::<Pixel>(image.data(), &mut accumulation, &aovs),
+            Format::Pfm => {
+                self.render_into::<Color<3, f32>>(image.data(), &mut accumulation, &aovs);
+            }
+        }
+
+        if self.adaptive.is_some() {
+            let accumulators = accumulation.data();
+            #[expect(clippy::cast_precision_loss)]
+            let average = accumulators
+                .iter()
+                .map(|accumulator| accumulator.samples as f64)
+                .sum::<f64>()
+                / accumulators.len() as f64;
+            println!("\nAdaptive sampling: {average:.1} samples per pixel on average");
+        }
+
+        if let Some(denoiser) = self.denoiser {
+            let aovs = aovs.into_inner().unwrap();
+            match self.output.format {
+                Format::Ppm => self.write_denoised::<Pixel>(denoiser, accumulation.data(), &aovs),
+                Format::Pfm => {
+                    self.write_denoised::<Color<3, f32>>(denoiser, accumulation.data(), &aovs);
+                }
+            }
+        }
+    }
+
+    /// Writes the denoised image next to the output, guided by the albedo and normal aovs
+    fn write_denoised<P: ImagePixel>(
+        &self,
+        denoiser: Denoiser,
+        accumulators: &[Accumulator],
+        aovs: &[&mut [Color<3, f32>]],
+    ) {
+        println!("\nDenoising");
+
+        let width = self.screen.resolution_width;
+        let pixels = width * self.screen.resolution_height;
+        // between rows from top to bottom and the image order, in both directions
+        #[expect(clippy::integer_division)]
+        let index = |bottom_up: bool, i: usize| {
+            if bottom_up {
+                (self.screen.resolution_height - 1 - i / width) * width + i % width
+            } else {
+                i
+            }
+        };
+
+        let color = (0..pixels)
+            .map(|i| accumulators[index(P::BOTTOM_UP, i)].color())
+            .collect::<Vec<_>>();
+        let guide = |aov| {
+            let image = &aovs[self.aovs.iter().position(|&other| other == aov).unwrap()];
+            (0..pixels)
+                .map(|i| image[index(true, i)])
+                .collect::<Vec<_>>()
+        };
+
+        let denoised = denoiser.denoise(width, &color, &guide(Aov::Albedo), &guide(Aov::Normal));
+
+        let output = Output {
+            path: Path::new(&self.output.path)
+                .with_extension(format!("denoised.{}", self.output.format.extension()))
+                .to_string_lossy()
+                .into_owned(),
+            format: self.output.format,
+        };
+        let mut image = Image::new(&output, width, self.screen.resolution_height);
+        for (i, pixel) in image.data::<P>().iter_mut().enumerate() {
+            *pixel = P::from_color(denoised[index(P::BOTTOM_UP, i)]);
+        }
+    }
+
+    /// Resets the accumulators or picks up the continued image, and writes the first checkpoint
+    fn start_accumulation<P: ImagePixel>(
+        &self,
+        data: &[P],
+        accumulators: &mut [Accumulator],
+        created: bool,
+    ) {
+        match self.continue_sampling {
+            // renders from before the accumulation file existed only have their image
+            Some(samples) if created => {
+                for (i, (accumulator, pixel)) in accumulators.iter_mut().zip(data).enumerate() {
+                    *accumulator = Accumulator::from_color(
+                        pixel.to_color(),
+                        samples as u64,
+                        self.filter_weight(self.pixel::<P>(i), samples),
+                    );
+                }
+            }
+            Some(_) => {}
+            None => accumulators.fill(Accumulator::default()),
+        }
 
-        let mut image = Image::new(self.screen.resolution_width, self.screen.resolution_height);
-        let data = image.data();
+        self.write_checkpoint(self.continue_sampling.unwrap_or(0));
+    }
 
-        let num_threads: usize = available_parallelism().unwrap().into();
+    fn render_into<P: ImagePixel>(
+        &self,
+        data: &mut [P],
+        accumulation: &mut Accumulation,
+        aovs: &Mutex<Vec<&mut [Color<3, f32>]>>,
+    ) {
+        let created = accumulation.created;
+        let accumulators = accumulation.data();
+
+        if !self.resume {
+            self.start_accumulation(data, accumulators, created);
+        }
+
+        let num_cpus: usize = available_parallelism().unwrap().into();
+        let num_threads = self.threads.map_or(num_cpus, NonZeroUsize::get);
 
         #[expect(clippy::integer_division)]
-        let chunk_size = (self.screen.resolution_width * self.screen.resolution_height)
-            / (num_threads * num_threads);
+        let chunk_size = ((self.screen.resolution_width * self.screen.resolution_height)
+            / (num_threads * num_threads))
+            .max(1);
 
+        // both are in image order
         let chunks = data
             .chunks_mut(chunk_size)
+            .zip(accumulators.chunks_mut(chunk_size))
             .map(Mutex::new)
             .collect::<Vec<_>>();
 
@@ -212,6 +750,8 @@ impl Scene {
 
         let total_work = chunks.len() * num_sample_chunks;
         let work_counter = AtomicUsize::new(0);
+        let passes = Passes::new(num_sample_chunks);
+        let start = Instant::now();
 
         let cpu = AtomicUsize::new(0);
         thread::scope(|scope| {
@@ -219,160 +759,344 @@ impl Scene {
                 scope.spawn(|| {
                     // set cpu affinity
                     let cpu = cpu.fetch_add(1, Ordering::Relaxed);
-                    set_cpu_affinity(cpu);
+                    set_cpu_affinity(cpu % num_cpus);
 
                     let mut bvh_stack = Vec::new();
+                    // the aov sums of the chunk's pixels, and the pixels they belong to
+                    let mut aov_sums = Vec::new();
+                    let mut aov_pixels = Vec::new();
 
                     loop {
                         let work_index = work_counter.fetch_add(1, Ordering::Relaxed);
-                        if work_index >= total_work {
+                        if work_index >= total_work || passes.stopped.load(Ordering::Relaxed) {
                             break;
                         }
 
                         #[expect(clippy::integer_division)]
-                        let sample_iteration =
-                            (work_index / chunks.len()) + self.continue_sampling.unwrap_or(0);
+                        let sample_iteration = work_index / chunks.len();
                         let chunk_index = work_index % chunks.len();
 
+                        // the chunks of the first pass are all started by now, so every pixel gets written
+                        if sample_iteration > 0
+                            && self
+                                .termination
+                                .time_limit
+                                .is_some_and(|TimeLimit(limit)| start.elapsed() >= limit)
+                        {
+                            passes.stop("Reached the time limit");
+                            break;
+                        }
+
+                        // continued renders start after the samples that are already done
+                        let first_sample = self.continue_sampling.unwrap_or(0)
+                            + sample_iteration * sample_chunk_size;
+
                         // report progress
                         if chunk_index == chunks.len() - 1 {
-                            print!(
-                                "\rSamples: {}",
-                                self.continue_sampling.unwrap_or(0)
-                                    + (sample_iteration + 1 - self.continue_sampling.unwrap_or(0))
-                                        * sample_chunk_size
-                            );
+                            print!("\rSamples: {}", first_sample + sample_chunk_size);
                             stdout().flush().unwrap();
                         }
 
                         let mut chunk = chunks[chunk_index].lock().unwrap();
 
+                        aov_sums.clear();
+                        aov_sums.resize(chunk.0.len() * self.aovs.len(), [0.; 3]);
+                        aov_pixels.clear();
+
                         // For every (x,y) pixel
-                        for i in 0..chunk.len() {
+                        for i in 0..chunk.0.len() {
                             // correct offset
-                            let offset_i = chunk_index * chunk_size + i;
-
-                            let x = offset_i % self.screen.resolution_width;
-                            #[expect(clippy::integer_division)]
-                            let y = offset_i / self.screen.resolution_width;
-
-                            let color = Color::new(
-                                std::iter::repeat_with(|| {
-                                    let pixel_position = self.screen.top_left
-                                                + row_step * (x as f32 + f32::random() / 2.) // Add random variation
-                                                + column_step * (y as f32 + f32::random() / 2.);
-
-                                    let ray = Ray::new(
-                                        self.camera.position,
-                                        self.camera.position.vector_to(pixel_position).normalize(),
-                                    );
-
-                                    self.ray_color(ray, &self.materials, &mut bvh_stack)
-                                })
-                                .take(sample_chunk_size)
-                                // average colors
-                                .reduce(|acc, element| {
-                                    Color::new(array::from_fn(|index| {
-                                        // by first adding them up
-                                        acc.inner()[index] + element.inner()[index]
-                                    }))
-                                })
-                                .unwrap_or_else(|| BaseVector::new([0.; 3]))
-                                .into_inner()
-                                // and then dividing by samples
-                                .map(|e| e / sample_chunk_size as f32),
-                            );
+                            let [x, y] = self.pixel::<P>(chunk_index * chunk_size + i);
 
-                            if self.incremental.is_some() || self.continue_sampling.is_some() {
-                                // average with last iteration
-                                chunk[i] = ((chunk[i].to_float_color::<f32>()
-                                    * sample_iteration as f32
-                                    + color.color_correct())
-                                    / (sample_iteration as f32 + 1.))
-                                    .to_natural_color::<u8>();
-                            } else {
-                                chunk[i] = color.color_correct().to_natural_color::<u8>();
+                            // resumed pixels might have done some samples of this pass already
+                            let samples = (first_sample as u64).max(chunk.1[i].samples)
+                                ..(first_sample + sample_chunk_size) as u64;
+                            if samples.is_empty() || self.is_converged(&chunk.1[i]) {
+                                continue;
                             }
+
+                            let sums = self.sample_pixel(
+                                [x, y],
+                                samples.clone(),
+                                &mut aov_sums[i * self.aovs.len()..(i + 1) * self.aovs.len()],
+                                &mut bvh_stack,
+                            );
+
+                            // the image is always written from the full-precision sums
+                            let accumulator = &mut chunk.1[i];
+                            aov_pixels.push((i, [x, y], accumulator.samples, samples.clone()));
+                            accumulator.add(&sums);
+                            chunk.0[i] = P::from_color(accumulator.color());
                         }
+                        let noise = chunk.1.iter().map(Accumulator::noise).sum();
+                        drop(chunk);
+
+                        if !self.aovs.is_empty() {
+                            self.write_aovs(&mut aovs.lock().unwrap(), &aov_pixels, &aov_sums);
+                        }
+
+                        self.finish_chunk(
+                            &passes,
+                            sample_iteration,
+                            chunks.len(),
+                            sample_chunk_size,
+                            noise,
+                        );
                     }
                 });
             }
         });
     }
 
-    #[inline(always)]
-    fn ray_color(
+    /// The pixel at `index` of an image of `P`
+    const fn pixel<P: ImagePixel>(&self, index: usize) -> [usize; 2] {
+        let x = index % self.screen.resolution_width;
+        #[expect(clippy::integer_division)]
+        let y = index / self.screen.resolution_width;
+
+        if P::BOTTOM_UP {
+            [x, self.screen.resolution_height - 1 - y]
+        } else {
+            [x, y]
+        }
+    }
+
+    /// If adaptive sampling is done with the pixel
+    fn is_converged(&self, accumulator: &Accumulator) -> bool {
+        self.adaptive
+            .is_some_and(|adaptive| adaptive.is_converged(accumulator.samples, accumulator.noise()))
+    }
+
+    /// Counts the chunk and the noise of its pixels as finished.
+    /// Once all passes up to its pass are done, writes a checkpoint and stops the render if the noise target is reached.
+    #[expect(clippy::cast_precision_loss)]
+    fn finish_chunk(
         &self,
-        ray: Ray,
-        materials: &[Material],
-        bvh_stack: &mut Vec<(f32, u32)>, // is reused across shape types
-    ) -> Color<3, f32> {
-        let mut current_ray = ray;
-        let mut current_color = None;
-
-        for _ in 0..self.screen.max_bounces {
-            let nearest_intersection = BvhNode::closest_shape(
-                &current_ray,
-                &self.shapes.spheres,
-                &self.bvhs.spheres,
-                bvh_stack,
+        passes: &Passes,
+        sample_iteration: usize,
+        chunks: usize,
+        sample_chunk_size: usize,
+        noise: f64,
+    ) {
+        *passes.noise[sample_iteration].lock().unwrap() += noise;
+        if passes.finished_chunks[sample_iteration].fetch_add(1, Ordering::AcqRel) + 1 != chunks {
+            return;
+        }
+
+        let mut finished = passes.finished.lock().unwrap();
+        let previous = *finished;
+        while passes
+            .finished_chunks
+            .get(*finished)
+            .is_some_and(|finished| finished.load(Ordering::Acquire) == chunks)
+        {
+            *finished += 1;
+        }
+
+        self.write_checkpoint(self.continue_sampling.unwrap_or(0) + *finished * sample_chunk_size);
+
+        // the noise of the last finished pass is complete
+        if *finished > previous
+            && let Some(target) = self.termination.noise_target
+        {
+            let pixels = self.screen.resolution_width * self.screen.resolution_height;
+            let noise = *passes.noise[*finished - 1].lock().unwrap() / pixels as f64;
+
+            if noise < target {
+                passes.stop("Reached the noise target");
+            }
+        }
+    }
+
+    /// Updates the aov images with the sums of the chunk's pixels.
+    /// `pixels` are the indices into the chunk, the pixels, their previous samples and the new samples.
+    #[expect(clippy::cast_precision_loss)]
+    fn write_aovs(
+        &self,
+        images: &mut [&mut [Color<3, f32>]],
+        pixels: &[(usize, [usize; 2], u64, Range<u64>)],
+        sums: &[[f32; 3]],
+    ) {
+        for &(i, [x, y], previous, ref samples) in pixels {
+            // pfm rows are stored from bottom to top
+            let index = (self.screen.resolution_height - 1 - y) * self.screen.resolution_width + x;
+            let sums = &sums[i * self.aovs.len()..(i + 1) * self.aovs.len()];
+
+            for ((aov, image), sum) in self.aovs.iter().zip(&mut *images).zip(sums) {
+                let pixel = &mut image[index];
+
+                if aov.is_averaged() {
+                    // the running average over all samples of the pixel
+                    let total = (previous + samples.end - samples.start) as f32;
+                    *pixel = Color::new(array::from_fn(|channel| {
+                        (pixel.inner()[channel] * previous as f32 + sum[channel]) / total
+                    }));
+                } else if previous == 0 {
+                    *pixel = Color::new(*sum);
+                }
+            }
+        }
+    }
+
+    /// Traces the samples of the pixel, returning their sums.
+    /// Also adds the aovs of the samples to `aov_sums`, or sets the ones which aren't averaged from the first sample.
+    // The only precision loss is turning the resolution into floats, which is fine
+    #[expect(clippy::cast_precision_loss)]
+    fn sample_pixel(
+        &self,
+        [x, y]: [usize; 2],
+        samples: Range<u64>,
+        aov_sums: &mut [[f32; 3]],
+        bvh_stack: &mut Vec<(f32, u32)>,
+    ) -> Accumulator {
+        let first_sample = samples.start;
+        let width = self.screen.resolution_width as f32;
+        let height = self.screen.resolution_height as f32;
+
+        let pixel = (y * self.screen.resolution_width + x) as u64;
+        #[expect(clippy::cast_possible_truncation)]
+        let samples_per_pixel = self.screen.samples_per_pixel as u32;
+
+        samples
+            .map(|sample| {
+                // makes every sample independent of the thread rendering it
+                sampler::start_sample(self.sampler, pixel, sample, samples_per_pixel, self.seed);
+
+                // spread the samples over the filter's extent around the pixel center
+                let ([offset_x, offset_y], weight) = self.filter.sample(sampler::get_2d());
+                let screen_coordinates = [
+                    (x as f32 + 0.5 + offset_x) / width,
+                    (y as f32 + 0.5 + offset_y) / height,
+                ];
+
+                // outside of the projection's image
+                let (color, first_hit) = self
+                    .camera
+                    .ray(screen_coordinates, &self.screen)
+                    .map_or_else(
+                        || (Color::new([0.; 3]), None),
+                        |ray| self.integrator.ray_color(self, ray, bvh_stack),
+                    );
+
+                for (sum, aov) in aov_sums.iter_mut().zip(&self.aovs) {
+                    let value = aov.value(self, first_hit.as_ref());
+
+                    if aov.is_averaged() {
+                        *sum = array::from_fn(|channel| sum[channel] + value[channel]);
+                    } else if sample == first_sample {
+                        *sum = value;
+                    }
+                }
+
+                (color, weight)
+            })
+            .fold(Accumulator::default(), |mut sums, (color, weight)| {
+                sums.add_sample(color, weight);
+                sums
+            })
+    }
+
+    /// The summed filter weight of the first `samples` samples of the pixel,
+    /// which only depends on the sampler, so it is the same as when they were rendered
+    fn filter_weight(&self, [x, y]: [usize; 2], samples: usize) -> f64 {
+        let pixel = (y * self.screen.resolution_width + x) as u64;
+        #[expect(clippy::cast_possible_truncation)]
+        let samples_per_pixel = samples as u32;
+
+        (0..samples as u64)
+            .map(|sample| {
+                sampler::start_sample(self.sampler, pixel, sample, samples_per_pixel, self.seed);
+                f64::from(self.filter.sample(sampler::get_2d()).1)
+            })
+            .sum()
+    }
+
+    /// The closest intersection of the ray, and the shape it is on
+    #[expect(clippy::type_complexity)]
+    fn closest_intersection(
+        &self,
+        ray: &Ray,
+        bvh_stack: &mut Vec<(f32, u32)>,
+    ) -> Option<(
+        (f32, Point3, (NormalizedVector3, [f32; 2]), MaterialIndexer),
+        ShapeIndex,
+    )> {
+        self.closest_intersection_counting(ray, bvh_stack, &mut [0; 2])
+    }
+    /// `closest_intersection`, adding the amount of visited bvh nodes and intersection tested shapes to `visits`
+    #[expect(clippy::type_complexity)]
+    fn closest_intersection_counting(
+        &self,
+        ray: &Ray,
+        bvh_stack: &mut Vec<(f32, u32)>,
+        visits: &mut [u32; 2],
+    ) -> Option<(
+        (f32, Point3, (NormalizedVector3, [f32; 2]), MaterialIndexer),
+        ShapeIndex,
+    )> {
+        BvhNode::closest_shape_counting(
+            ray,
+            &self.shapes.spheres,
+            &self.bvhs.spheres,
+            bvh_stack,
+            visits,
+        )
+        .map(|(distance, point, normal, material, index)| {
+            (
+                (distance, point, normal, material),
+                ShapeIndex::Sphere(index),
             )
-            .into_iter()
-            .chain(BvhNode::closest_shape(
-                &current_ray,
+        })
+        .into_iter()
+        .chain(
+            BvhNode::closest_shape_counting(
+                ray,
                 &self.shapes.planes,
                 &self.bvhs.planes,
                 bvh_stack,
-            ))
-            .chain(BvhNode::closest_shape(
-                &current_ray,
+                visits,
+            )
+            .map(|(distance, point, normal, material, index)| {
+                (
+                    (distance, point, normal, material),
+                    ShapeIndex::Plane(index),
+                )
+            }),
+        )
+        .chain(
+            BvhNode::closest_shape_counting(
+                ray,
                 &self.shapes.triangles,
                 &self.bvhs.triangles,
                 bvh_stack,
-            ))
-            .min_by(|&(a, ..), &(b, ..)| a.partial_cmp(&b).unwrap());
-
-            match nearest_intersection {
-                // skybox
-                None => {
-                    let a = 0.5 * (current_ray.direction.y() + 1.0); // y scaled to 0.5-1
-
-                    let current_color = current_color.get_or_insert_with(|| Color::new([1.; 3]));
-                    *current_color = *current_color
-                        * (Color::new([0.2, 0.2, 0.8]) * (1.0 - a) + Color::new([1.; 3]) * a);
+                visits,
+            )
+            .map(|(distance, point, normal, material, index)| {
+                (
+                    (distance, point, normal, material),
+                    ShapeIndex::Triangle(index),
+                )
+            }),
+        )
+        .min_by(|&((a, ..), _), &((b, ..), _)| a.partial_cmp(&b).unwrap())
+    }
 
-                    break;
-                }
-                // scattter
-                Some((_, hit_point, (normal, texture_coordinates), shape_material_index)) => {
-                    let shape_material: &Material = shape_material_index.index(materials);
-
-                    match shape_material.scatter(&current_ray, normal, hit_point) {
-                        Scatter::Scattered(ray, color) => {
-                            // calculate color of scattered ray and mix it with the current color
-                            let current_color =
-                                current_color.get_or_insert_with(|| Color::new([1.; 3]));
-                            *current_color = *current_color * color.sample(texture_coordinates);
-
-                            current_ray = ray;
-                        }
-                        Scatter::Absorbed => {
-                            current_color = Some(Color::new([0.; 3]));
-                            break;
-                        }
-                        Scatter::Light(color) => {
-                            let current_color =
-                                current_color.get_or_insert_with(|| Color::new([1.; 3]));
-                            *current_color = *current_color * color.sample(texture_coordinates);
-                            break;
-                        }
-                    }
-                }
-            }
-        }
+    /// The pdf of light sampling finding `light` along the ray, after `distance`
+    #[expect(clippy::cast_precision_loss)]
+    fn light_pdf(&self, light: Light, ray: &Ray, distance: f32) -> f32 {
+        light.direction_pdf(&self.shapes, ray.origin, ray.direction, distance)
+            / self.shapes.lights.len() as f32
+    }
+}
 
-        current_color.unwrap_or_else(|| Color::new([0.; 3]))
+/// The weight of a sample drawn with `pdf`, which could also have been drawn with `other_pdf` (Veach 1997)
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    if pdf <= 0. {
+        return 0.;
     }
+    let squared = pdf * pdf;
+
+    squared / (squared + other_pdf * other_pdf)
 }
 
 #[derive(Debug)]
@@ -405,14 +1129,483 @@ impl Screen {
             max_bounces,
         }
     }
+    /// Places the screen one unit in front of a look-at camera, matching its field of view and the aspect ratio of the resolution.
+    /// Does nothing for cameras without a look-at description.
+    // The only precision loss is turning the resolution into floats, which is fine
+    #[expect(clippy::cast_precision_loss)]
+    fn frame(&mut self, camera: &Camera) {
+        let Some(ref look_at) = camera.look_at else {
+            return;
+        };
+
+        let forward = camera.position.vector_to(look_at.target).normalize::<f32>();
+        let right = forward.to_vector().cross(look_at.up).normalize::<f32>();
+        let up = right.to_vector().cross(forward.to_vector());
+
+        // orthographic cameras show the extent the fov covers at the target
+        let distance = match camera.projection {
+            Projection::Orthographic => camera.position.vector_to(look_at.target).length(),
+            Projection::Perspective | Projection::Equirectangular | Projection::Fisheye { .. } => {
+                1.
+            }
+        };
+
+        let half_height = (look_at.vertical_fov.to_radians() / 2.).tan() * distance;
+        let half_width = half_height * self.resolution_width as f32 / self.resolution_height as f32;
+
+        self.top_left =
+            camera.position + forward * distance + up * half_height - right * half_width;
+        self.top_edge = right * (2. * half_width);
+        self.left_edge = up * (-2. * half_height);
+    }
+    /// Changes the resolution, keeping the aspect ratio of the pixels.
+    /// Look-at cameras are framed again, explicit screen corners are widened or narrowed around their center.
+    #[expect(clippy::cast_precision_loss)]
+    fn resize(&mut self, [width, height]: [usize; 2], camera: &Camera) {
+        if camera.look_at.is_none() {
+            let scale = (width as f32 / height as f32)
+                / (self.resolution_width as f32 / self.resolution_height as f32);
+            self.top_left = self.top_left + self.top_edge * ((1. - scale) / 2.);
+            self.top_edge = self.top_edge * scale;
+        }
+        self.resolution_width = width;
+        self.resolution_height = height;
+        self.frame(camera);
+    }
 }
 
 #[derive(Debug)]
 pub struct Camera {
     position: Point3,
+    look_at: Option<LookAt>,
+    /// A pinhole camera if None
+    lens: Option<Lens>,
+    projection: Projection,
 }
 impl Camera {
-    const fn new(position: Point3) -> Self {
-        Self { position }
+    const fn new(position: Point3, look_at: Option<LookAt>) -> Self {
+        Self {
+            position,
+            look_at,
+            lens: None,
+            projection: Projection::Perspective,
+        }
+    }
+    /// Returns the ray through `[u, v]` (0..=1 along the top and left edges of the screen).
+    /// None if the point lies outside of the projection's image.
+    fn ray(&self, [u, v]: [f32; 2], screen: &Screen) -> Option<Ray> {
+        let pixel_position = screen.top_left + screen.top_edge * u + screen.left_edge * v;
+
+        match self.projection {
+            Projection::Perspective => {
+                let direction = self.position.vector_to(pixel_position);
+
+                let Some(ref lens) = self.lens else {
+                    return Some(Ray::new(self.position, direction.normalize()));
+                };
+
+                let [right, up, forward] = self.basis(screen);
+
+                // all rays through the pixel converge on the focal plane
+                let focal_point =
+                    self.position + direction * (lens.focus_distance / direction.dot(forward));
+
+                let [x, y] = sampler::unit_disk(sampler::get_2d());
+                let origin = self.position
+                    + right * (x * lens.aperture_radius)
+                    + up * (y * lens.aperture_radius);
+
+                Some(Ray::new(origin, origin.vector_to(focal_point).normalize()))
+            }
+            Projection::Orthographic => {
+                let [_, _, forward] = self.basis(screen);
+
+                // move the pixel back onto the camera's plane
+                let depth = self.position.vector_to(pixel_position).dot(forward);
+
+                Some(Ray::new(pixel_position - forward * depth, forward))
+            }
+            Projection::Equirectangular => {
+                let [right, up, forward] = self.basis(screen);
+
+                let longitude = (u - 0.5) * TAU;
+                let latitude = (0.5 - v) * PI;
+
+                let direction = forward * (latitude.cos() * longitude.cos())
+                    + right * (latitude.cos() * longitude.sin())
+                    + up * latitude.sin();
+
+                Some(Ray::new(self.position, direction.normalize()))
+            }
+            Projection::Fisheye { fov } => {
+                let [right, up, forward] = self.basis(screen);
+
+                // centered, with a circle touching the shorter edges
+                let aspect_ratio = screen.top_edge.length::<f32>() / screen.left_edge.length();
+                let [x, y] = if aspect_ratio > 1. {
+                    [(2. * u - 1.) * aspect_ratio, 1. - 2. * v]
+                } else {
+                    [2. * u - 1., (1. - 2. * v) / aspect_ratio]
+                };
+
+                let radius = x.hypot(y);
+                if radius > 1. {
+                    return None;
+                }
+
+                // equidistant mapping
+                let theta = radius * fov.to_radians() / 2.;
+                let [x, y] = if radius > 0. {
+                    [x / radius, y / radius]
+                } else {
+                    [0.; 2]
+                };
+
+                let direction = forward * theta.cos() + (right * x + up * y) * theta.sin();
+
+                Some(Ray::new(self.position, direction.normalize()))
+            }
+        }
+    }
+    /// [right, up, forward] of the screen, with forward pointing from the camera towards it
+    fn basis(&self, screen: &Screen) -> [NormalizedVector3; 3] {
+        let right = screen.top_edge.normalize::<f32>();
+        let up = (-screen.left_edge).normalize::<f32>();
+        let normal = right.to_vector().cross(up.to_vector()).normalize::<f32>();
+
+        let forward = if normal.dot(self.position.vector_to(screen.top_left)) < 0. {
+            -normal
+        } else {
+            normal
+        };
+
+        [right, up, forward]
+    }
+}
+
+/// How rays are shot from the camera
+#[derive(Debug, PartialEq)]
+pub enum Projection {
+    Perspective,
+    /// Parallel rays along the view direction, covering the screen
+    Orthographic,
+    /// A 360° latitude-longitude panorama
+    Equirectangular,
+    /// An equidistant fisheye, with `fov` in degrees
+    Fisheye {
+        fov: f32,
+    },
+}
+impl TryFrom<&str> for Projection {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut split = value.split_whitespace();
+
+        let projection = match split.next() {
+            Some("perspective") => Self::Perspective,
+            Some("orthographic") => Self::Orthographic,
+            Some("equirectangular") => Self::Equirectangular,
+            Some("fisheye") => Self::Fisheye {
+                // defaults to a hemisphere
+                fov: match split.next() {
+                    Some(fov) => fov
+                        .parse()
+                        .ok()
+                        .filter(|&fov| fov > 0. && fov <= 360.)
+                        .ok_or_else(|| {
+                            ParseError::new("fisheye followed by its fov (0 to 360 degrees)", value)
+                        })?,
+                    None => 180.,
+                },
+            },
+            _ => {
+                return Err(ParseError::new(
+                    "one of perspective, orthographic, equirectangular or fisheye",
+                    value,
+                ));
+            }
+        };
+
+        // no trailing parameters
+        match split.next() {
+            None => Ok(projection),
+            Some(_) => Err(ParseError::new("no further projection parameters", value)),
+        }
+    }
+}
+
+/// A thin lens, for depth of field
+#[derive(Debug)]
+pub struct Lens {
+    aperture_radius: f32,
+    /// The distance from the camera to the plane in focus
+    focus_distance: f32,
+}
+impl Lens {
+    const fn new(aperture_radius: f32, focus_distance: f32) -> Self {
+        Self {
+            aperture_radius,
+            focus_distance,
+        }
+    }
+}
+
+/// Describes the screen relative to the camera, instead of using explicit screen corners
+#[derive(Debug)]
+pub struct LookAt {
+    target: Point3,
+    up: Vector3,
+    /// In degrees
+    vertical_fov: f32,
+}
+impl LookAt {
+    const fn new(target: Point3, up: Vector3, vertical_fov: f32) -> Self {
+        Self {
+            target,
+            up,
+            vertical_fov,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{SCENE, TempDir};
+
+    fn output(directory: &TempDir) -> Output {
+        Output {
+            path: directory.join("out.pfm"),
+            format: Format::Pfm,
+        }
+    }
+
+    fn sampled(samples: &[f32]) -> Accumulator {
+        let mut accumulator = Accumulator::default();
+        for &sample in samples {
+            accumulator.add_sample(Color::new([sample; 3]), 1.);
+        }
+        accumulator
+    }
+
+    #[test]
+    fn noise_needs_two_samples() {
+        assert!(sampled(&[]).noise().is_infinite());
+        assert!(sampled(&[1.]).noise().is_infinite());
+        assert!(sampled(&[1., 2.]).noise().is_finite());
+        assert!(sampled(&[1., 1., 1.]).noise().abs() < 1e-9);
+    }
+
+    #[test]
+    fn noise_is_relative_error() {
+        // a mean of 2 with a standard error of 1
+        assert!((sampled(&[1., 3.]).noise() - 0.5).abs() < 1e-6);
+        assert!((sampled(&[10., 30.]).noise() - 0.5).abs() < 1e-6);
+        // absolute below the floor
+        assert!((sampled(&[0., 0.002]).noise() - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn accumulation_round_trip() {
+        let directory = TempDir::new("accumulation-round-trip");
+        let output = output(&directory);
+
+        let mut accumulation = Accumulation::new(&output, 2, 2);
+        assert!(accumulation.created);
+        for (i, accumulator) in accumulation.data().iter_mut().enumerate() {
+            #[expect(clippy::cast_precision_loss)]
+            let i = i as f32;
+            *accumulator = sampled(&[i, 2. * i, 0.5]);
+        }
+        drop(accumulation);
+
+        let mut accumulation = Accumulation::new(&output, 2, 2);
+        assert!(!accumulation.created);
+        for (i, accumulator) in accumulation.data().iter().enumerate() {
+            #[expect(clippy::cast_precision_loss)]
+            let i = i as f32;
+            let expected = sampled(&[i, 2. * i, 0.5]);
+            assert_eq!(accumulator.samples, 3);
+            assert_eq!(accumulator.color(), expected.color());
+            assert!((accumulator.noise() - expected.noise()).abs() < 1e-12);
+        }
+        drop(accumulation);
+
+        // another resolution starts over
+        assert!(Accumulation::new(&output, 3, 2).created);
+    }
+
+    /// Renders `scene` into `directory`, returning the bits of the image and the samples of every pixel
+    fn render(scene: &str, directory: &TempDir) -> (Vec<[u32; 3]>, Vec<u64>) {
+        let mut scene = config::parse(scene).unwrap();
+        scene.output = output(directory);
+        scene.render();
+
+        let [width, height] = [
+            scene.screen.resolution_width,
+            scene.screen.resolution_height,
+        ];
+        let mut image = Image::new(&scene.output, width, height);
+        let mut accumulation = Accumulation::new(&scene.output, width, height);
+        (
+            image
+                .data::<Color<3, f32>>()
+                .iter()
+                .map(|pixel| pixel.into_inner().map(f32::to_bits))
+                .collect(),
+            accumulation
+                .data()
+                .iter()
+                .map(|accumulator| accumulator.samples)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn time_limit_finishes_the_first_pass() {
+        let directory = TempDir::new("time-limit");
+        let (expected, _) = render(SCENE, &directory);
+
+        // the image of a previous render, which reads as nan
+        let stale = vec![0xff; fs::read(directory.join("out.pfm")).unwrap().len()];
+        for scene in [
+            format!("{SCENE}\ntime_limit(0s)"),
+            format!("{SCENE}\nincremental(2)\ntime_limit(0s)"),
+        ] {
+            fs::write(directory.join("out.pfm"), &stale).unwrap();
+            let (image, samples) = render(&scene, &directory);
+
+            for pixel in &image {
+                assert!(
+                    pixel.iter().all(|&bits| f32::from_bits(bits).is_finite()),
+                    "{scene}"
+                );
+            }
+            if scene.contains("incremental") {
+                assert!(samples.iter().all(|&samples| samples == 2), "{samples:?}");
+            } else {
+                assert_eq!(image, expected);
+                assert!(samples.iter().all(|&samples| samples == 8), "{samples:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn aovs_show_the_first_hit() {
+        let directory = TempDir::new("aovs");
+        for integrator in ["path", "ao", "debug normals"] {
+            render(
+                &format!("{SCENE}\naovs(depth, id)\nintegrator({integrator})\nfilter(box 0.01)"),
+                &directory,
+            );
+
+            let [depth, id] = [Aov::Depth, Aov::Id].map(|aov| {
+                Image::new(&aov.output(&output(&directory)), 4, 4)
+                    .data::<Color<3, f32>>()
+                    .iter()
+                    .map(|pixel| pixel.into_inner()[0])
+                    .collect::<Vec<_>>()
+            });
+            for (pixel, (&depth, &id)) in depth.iter().zip(&id).enumerate() {
+                let hit = ![0, 3, 12, 15].contains(&pixel);
+                // the camera is 4 in front of the sphere, which the corners miss close to their centers
+                assert_eq!(id >= 0., hit, "{integrator} {pixel}: {id}");
+                assert!(
+                    if hit {
+                        (4.0..5.).contains(&depth)
+                    } else {
+                        depth <= 0.
+                    },
+                    "{integrator} {pixel}: {depth}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn resume_rejects_other_samples() {
+        let directory = TempDir::new("resume-samples");
+        render(SCENE, &directory);
+
+        let resumed = |args: &[&str]| {
+            let mut scene = config::parse(SCENE).unwrap();
+            cli::Args::parse(args.iter().map(|&arg| arg.to_owned()))
+                .unwrap()
+                .apply(&mut scene)
+                .unwrap();
+            scene.output = output(&directory);
+            scene.resume()
+        };
+        assert_eq!(resumed(&[]).unwrap(), 8);
+        assert!(matches!(
+            resumed(&["-s", "16"]),
+            Err(ResumeError::SceneChanged)
+        ));
+    }
+
+    #[test]
+    fn hash_covers_referenced_files() {
+        let directory = TempDir::new("hash-files");
+        let texture = directory.write("texture.ppm", b"P6\n1 1\n255\n000");
+        let scene = SCENE.replace("0.5 0.5 0.5", &texture);
+
+        let hash = || config::parse(&scene).unwrap().hash();
+        let before = hash();
+        assert_eq!(hash(), before);
+
+        directory.write("texture.ppm", b"P6\n1 1\n255\n001");
+        assert_ne!(hash(), before);
+    }
+
+    #[test]
+    fn continuing_matches_rendering_at_once() {
+        // negative lobes, so the filter weights differ from the amount of samples
+        let scene = |samples| {
+            format!(
+                "{}\nfilter(mitchell)",
+                SCENE.replace("4, 4, 8, 2", &format!("4, 4, {samples}, 2"))
+            )
+        };
+        let directory = TempDir::new("continue");
+        let (expected, _) = render(&scene(16), &directory);
+
+        // with the accumulation file, and from the image alone like renders from before it existed
+        for keep_accumulation in [true, false] {
+            fs::remove_file(directory.join("out.pfm")).unwrap();
+            _ = fs::remove_file(Accumulation::path(&output(&directory)));
+            render(&scene(8), &directory);
+            if !keep_accumulation {
+                fs::remove_file(Accumulation::path(&output(&directory))).unwrap();
+            }
+
+            let mut continued = config::parse(&scene(8)).unwrap();
+            continued.output = output(&directory);
+            continued.continue_sampling = Some(8);
+            continued.render();
+
+            let image = Image::new(&continued.output, 4, 4)
+                .data::<Color<3, f32>>()
+                .to_vec();
+            for (pixel, expected) in image.iter().zip(&expected) {
+                for (&channel, &expected) in pixel.inner().iter().zip(expected) {
+                    let expected = f32::from_bits(expected);
+                    assert!(
+                        (channel - expected).abs() <= 1e-5 * expected.abs().max(1.),
+                        "{keep_accumulation}: {pixel:?} {expected}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn continued_image_estimates_noise_from_new_samples() {
+        let mut accumulator = Accumulator::from_color(Color::new([0.5; 3]), 16, 16.);
+        assert!(accumulator.noise().is_infinite());
+
+        accumulator.add(&sampled(&[0.25, 0.75]));
+        assert_eq!(accumulator.samples, 18);
+        assert!(accumulator.noise().is_finite());
     }
 }
@@ -0,0 +1,75 @@
+use crate::{
+    Ray, Shapes,
+    indices::Indexer,
+    material::Material,
+    shapes::{Intersects as _, Shape as _, Sphere, Triangle},
+    vec3::{NormalizedVector3, Point3},
+};
+
+/// An emissive shape, that can be sampled directly
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    Sphere(Indexer<u32, Sphere>),
+    Triangle(Indexer<u32, Triangle>),
+}
+impl Light {
+    /// Collects the spheres and triangles with light materials.
+    /// Has to be called after the bvhs are built, as they reorder the shapes.
+    #[expect(clippy::cast_possible_truncation)]
+    pub fn collect(
+        spheres: &[Sphere],
+        triangles: &[Triangle],
+        materials: &[Material],
+    ) -> Box<[Self]> {
+        let spheres = spheres
+            .iter()
+            .enumerate()
+            .filter(|&(_, sphere)| sphere.material_index().index(materials).is_light())
+            .map(|(index, _)| Self::Sphere(Indexer::new(index as u32)));
+        let triangles = triangles
+            .iter()
+            .enumerate()
+            .filter(|&(_, triangle)| triangle.material_index().index(materials).is_light())
+            .map(|(index, _)| Self::Triangle(Indexer::new(index as u32)));
+
+        spheres.chain(triangles).collect()
+    }
+    /// Samples a direction from `origin` towards the light, returning it and its pdf per solid angle
+    pub fn sample_direction(
+        &self,
+        shapes: &Shapes,
+        origin: Point3,
+        sample: [f32; 2],
+    ) -> Option<(NormalizedVector3, f32)> {
+        match *self {
+            Self::Sphere(index) => index
+                .index(&*shapes.spheres)
+                .sample_direction(origin, sample),
+            Self::Triangle(index) => index
+                .index(&*shapes.triangles)
+                .sample_direction(origin, sample),
+        }
+    }
+    /// The pdf per solid angle of `sample_direction` for `direction`, which hits the light after `distance`
+    pub fn direction_pdf(
+        &self,
+        shapes: &Shapes,
+        origin: Point3,
+        direction: NormalizedVector3,
+        distance: f32,
+    ) -> f32 {
+        match *self {
+            Self::Sphere(index) => index.index(&*shapes.spheres).direction_pdf(origin),
+            Self::Triangle(index) => index
+                .index(&*shapes.triangles)
+                .direction_pdf(direction, distance),
+        }
+    }
+    /// The distance at which the ray hits the light
+    pub fn intersects(&self, shapes: &Shapes, ray: &Ray) -> Option<f32> {
+        match *self {
+            Self::Sphere(index) => index.index(&*shapes.spheres).intersects(ray),
+            Self::Triangle(index) => index.index(&*shapes.triangles).intersects(ray),
+        }
+    }
+}
@@ -1,8 +1,53 @@
-use std::fs;
+use std::{env, fs, process};
 
-use raytracer::{SCENE, config};
+use raytracer::{
+    SCENE,
+    cli::{Args, CliError, USAGE},
+    config,
+};
 
 fn main() {
-    let scene = SCENE.get_or_init(|| config::parse(&fs::read_to_string("scene").unwrap()));
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(CliError::Help) => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(error) => {
+            eprintln!("Error: {error}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    let string = match fs::read_to_string(&args.scene) {
+        Ok(string) => string,
+        Err(error) => {
+            eprintln!("Error reading {}: {error}", args.scene);
+            process::exit(1);
+        }
+    };
+
+    let mut scene = match config::parse(&string) {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("Error in {}: {error}", args.scene);
+            process::exit(1);
+        }
+    };
+    if let Err(error) = args.apply(&mut scene) {
+        eprintln!("Error: {error}");
+        process::exit(2);
+    }
+    if args.resume {
+        match scene.resume() {
+            Ok(samples) => println!("Resuming after {samples} samples per pixel"),
+            Err(error) => {
+                eprintln!("Error: {error}");
+                process::exit(1);
+            }
+        }
+    }
+
+    let scene = SCENE.get_or_init(|| scene);
     scene.render();
 }
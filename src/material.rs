@@ -1,10 +1,21 @@
-use std::{fs, ops::Neg as _};
+use std::{
+    array,
+    f32::consts::{PI, TAU},
+    fs, io, iter,
+    ops::Neg as _,
+    str::SplitWhitespace,
+};
 
 use crate::{
     Ray,
+    config::ParseError,
     indices::HasIndexer,
+    media::Medium,
+    microfacet::{self, Frame, Ggx},
     mmap::Pixel,
-    rng::Random as _,
+    principled::Principled,
+    rng, sampler,
+    spectrum::RefractiveIndex,
     vec3::{Color, Lerp as _, New as _, NormalizedVector3, Point3},
 };
 
@@ -18,31 +29,181 @@ impl Material {
     pub const fn new(kind: MaterialKind, color_kind: ColorKind) -> Self {
         Self { kind, color_kind }
     }
+    /// Identifies the pixels of the textures, which the scene only names by their files
+    pub fn texture_hash(&self) -> u64 {
+        let principled = if let MaterialKind::Principled(ref principled) = self.kind {
+            Some(principled.textures())
+        } else {
+            None
+        };
+        rng::hash(
+            iter::once(&self.color_kind)
+                .chain(principled.into_iter().flatten())
+                .map(ColorKind::hash),
+        )
+    }
+
+    pub const fn is_light(&self) -> bool {
+        matches!(self.kind, MaterialKind::Light)
+    }
+    /// The inside of the material, if light can travel through it
+    pub fn medium(&self) -> Option<Medium> {
+        match self.kind {
+            MaterialKind::Glass {
+                refractive_index,
+                absorption,
+                priority,
+                ..
+            } => Some(Medium {
+                refractive_index,
+                absorption,
+                priority,
+            }),
+            MaterialKind::Principled(ref principled) if principled.is_transmissive() => {
+                Some(Medium {
+                    refractive_index: principled.refractive_index.inside(),
+                    absorption: None,
+                    priority: 0,
+                })
+            }
+            MaterialKind::Lambertian
+            | MaterialKind::Metal { .. }
+            | MaterialKind::Conductor { .. }
+            | MaterialKind::Principled(_)
+            | MaterialKind::Light => None,
+        }
+    }
+    /// The color of the material at the texture coordinates, without any lighting
+    pub fn albedo(&self, texture_coordinates: [f32; 2]) -> Color<3, f32> {
+        self.color_kind.sample(texture_coordinates)
+    }
+    /// The bsdf times the cosine to the normal, and the pdf of `scatter` returning `direction`.
+    /// None if the material only scatters into single directions, so there is nothing to evaluate.
+    pub fn evaluate(
+        &self,
+        ray: &Ray,
+        normal: NormalizedVector3,
+        direction: NormalizedVector3,
+        texture_coordinates: [f32; 2],
+        outside_refractive_index: f32,
+        wavelength: Option<f32>,
+    ) -> Option<(Color<3, f32>, f32)> {
+        let color = self.color_kind.sample(texture_coordinates);
+
+        match self.kind {
+            MaterialKind::Lambertian => {
+                let normal = facing(ray, normal);
+                let cos = normal.dot(direction).max(0.);
+
+                // cosine sampling matches the lambertian brdf exactly
+                Some((color * (cos / PI), cos / PI))
+            }
+            MaterialKind::Metal { fuzziness } if fuzziness > 0. => {
+                let pdf = if direction.dot(normal) > 0. {
+                    fuzzy_reflection_pdf(ray.direction.reflect(normal), direction, fuzziness)
+                } else {
+                    0.
+                };
+
+                Some((color * pdf, pdf))
+            }
+            MaterialKind::Conductor {
+                distribution,
+                eta,
+                k,
+            } if !distribution.is_smooth() => {
+                let frame = Frame::new(facing(ray, normal));
+                let wo = frame.to_local(-ray.direction);
+                let wi = frame.to_local(direction);
+                if wo[2] <= 0. || wi[2] <= 0. {
+                    return Some((Color::new([0.; 3]), 0.));
+                }
+
+                let h = microfacet::normalize(array::from_fn(|i| wo[i] + wi[i]));
+                let d = distribution.d(h);
+
+                Some((
+                    color
+                        * conductor_fresnel(microfacet::dot(wo, h), eta, k)
+                        * (d * distribution.g2(wo, wi) / (4. * wo[2])),
+                    distribution.g1(wo) * d / (4. * wo[2]),
+                ))
+            }
+            MaterialKind::Glass {
+                refractive_index,
+                distribution,
+                ..
+            } if !distribution.is_smooth() => {
+                // relative to the medium around it
+                let refractive_index = refractive_index.at(wavelength) / outside_refractive_index;
+                let (eta, normal) = relative_refractive_index(ray, normal, refractive_index);
+                let frame = Frame::new(normal);
+                let (value, pdf) = microfacet::evaluate_rough_dielectric(
+                    distribution,
+                    eta,
+                    frame.to_local(-ray.direction),
+                    frame.to_local(direction),
+                );
+
+                Some((color * value, pdf))
+            }
+            MaterialKind::Principled(ref principled) => {
+                // relative to the medium around it
+                let refractive_index = principled
+                    .refractive_index
+                    .at(wavelength, texture_coordinates)
+                    / outside_refractive_index;
+                let (eta, normal) = relative_refractive_index(ray, normal, refractive_index);
+                let frame = Frame::new(normal);
+
+                Some(principled.at(color, texture_coordinates).evaluate(
+                    eta,
+                    frame.to_local(-ray.direction),
+                    frame.to_local(direction),
+                ))
+            }
+            MaterialKind::Metal { .. }
+            | MaterialKind::Conductor { .. }
+            | MaterialKind::Glass { .. }
+            | MaterialKind::Light => None,
+        }
+    }
 
-    /// Returns the scattered ray, if it wasn't absorbed or the light color
-    pub fn scatter<'a>(
-        &'a self,
+    /// Returns the scattered ray, if it wasn't absorbed, or the light color.
+    ///
+    /// `outside_refractive_index` is the one of the medium on the other side of the surface, 1 for air,
+    /// and `wavelength` the one a spectral path takes refractive indices at.
+    #[expect(clippy::too_many_lines)]
+    pub fn scatter(
+        &self,
         ray: &Ray,
         normal: NormalizedVector3,
         hit_point: Point3,
-    ) -> Scatter<'a> {
-        let hit_point = hit_point + normal.to_vector() * 1e-4;
+        texture_coordinates: [f32; 2],
+        outside_refractive_index: f32,
+        wavelength: Option<f32>,
+    ) -> Scatter {
+        let origin = hit_point + normal.to_vector() * 1e-4;
+        let color = self.color_kind.sample(texture_coordinates);
 
         match self.kind {
             MaterialKind::Lambertian => {
-                let direction = (normal + NormalizedVector3::random()).normalize::<f32>();
+                // scatter back to the side the ray came from
+                let normal = facing(ray, normal);
+                let direction =
+                    (normal + sampler::unit_sphere(sampler::get_2d())).normalize::<f32>();
+                // Avoid division by zero etc.
+                let direction = if direction.to_vector().near_zero() {
+                    normal
+                } else {
+                    direction
+                };
 
+                // cosine distributed
                 Scatter::Scattered(
-                    Ray::new(
-                        hit_point,
-                        // Avoid division by zero etc.
-                        if direction.to_vector().near_zero() {
-                            normal
-                        } else {
-                            direction
-                        },
-                    ),
-                    &self.color_kind,
+                    Ray::new(origin, direction),
+                    color,
+                    Some(normal.dot(direction).max(0.) / PI),
                 )
             }
             MaterialKind::Metal { fuzziness } => {
@@ -50,52 +211,108 @@ impl Material {
                 let direction = ray.direction.reflect(normal);
 
                 if fuzziness == 0.0 {
-                    Scatter::Scattered(Ray::new(hit_point, direction), &self.color_kind)
+                    Scatter::Scattered(Ray::new(origin, direction), color, None)
                 } else {
                     // add fuzziness
-                    let direction =
-                        (direction + NormalizedVector3::random() * fuzziness).normalize();
+                    let fuzzy_direction = (direction
+                        + sampler::unit_sphere(sampler::get_2d()) * fuzziness)
+                        .normalize();
 
                     // Return None if the ray would end up in the object
-                    if direction.dot(normal) > 0. {
-                        Scatter::Scattered(Ray::new(hit_point, direction), &self.color_kind)
+                    if fuzzy_direction.dot(normal) > 0. {
+                        Scatter::Scattered(
+                            Ray::new(origin, fuzzy_direction),
+                            color,
+                            Some(fuzzy_reflection_pdf(direction, fuzzy_direction, fuzziness)),
+                        )
                     } else {
                         Scatter::Absorbed
                     }
                 }
             }
-            MaterialKind::Glass { refractive_index } => {
-                // If it enters or exits the shape
-                let (refractive_index, normal) = if ray.direction.dot(normal) < 0. {
-                    (1. / refractive_index, normal)
-                } else {
-                    (refractive_index, -normal)
-                };
-
-                let cos = ray.direction.neg().dot(normal).min(1.);
-                let sin = (1. - cos * cos).sqrt();
+            MaterialKind::Glass {
+                refractive_index,
+                distribution,
+                ..
+            } => {
+                // relative to the medium around it
+                let refractive_index = refractive_index.at(wavelength) / outside_refractive_index;
+                let (eta, normal) = relative_refractive_index(ray, normal, refractive_index);
+
+                if distribution.is_smooth() {
+                    let direction = sample_smooth_dielectric(ray, normal, eta);
+                    return Scatter::Scattered(
+                        dielectric_ray(hit_point, normal, direction),
+                        color,
+                        None,
+                    );
+                }
 
-                // schlick approximation
-                let reflectance = {
-                    let r0 = (1. - refractive_index) / (1. + refractive_index);
-                    let r0 = r0 * r0;
-                    r0 + (1. - r0) * (1. - cos).powi(5)
-                };
+                let frame = Frame::new(normal);
+                let sample = microfacet::sample_rough_dielectric(
+                    distribution,
+                    eta,
+                    frame.to_local(-ray.direction),
+                );
+                scattered_locally(
+                    (hit_point, normal),
+                    &frame,
+                    sample.map(|(direction, weight, pdf)| (direction, color * weight, pdf)),
+                )
+            }
+            MaterialKind::Conductor {
+                distribution,
+                eta,
+                k,
+            } => {
+                let normal = facing(ray, normal);
+
+                if distribution.is_smooth() {
+                    let cos = ray.direction.neg().dot(normal);
+                    return Scatter::Scattered(
+                        Ray::new(origin, ray.direction.reflect(normal)),
+                        color * conductor_fresnel(cos, eta, k),
+                        None,
+                    );
+                }
 
-                let direction = if refractive_index * sin > 1.0 || f32::random() < reflectance {
-                    ray.direction.reflect(normal)
-                } else {
-                    // refract
-                    let perpendicular = (ray.direction + normal * cos) * refractive_index;
-                    let discriminant = 1. - refractive_index * refractive_index * (1. - cos * cos);
-                    let parallel = normal * -discriminant.sqrt();
+                let frame = Frame::new(normal);
+                let wo = frame.to_local(-ray.direction);
+                if wo[2] <= 0. {
+                    return Scatter::Absorbed;
+                }
 
-                    NormalizedVector3::new(perpendicular + parallel)
-                };
+                let h = distribution.sample_visible_normal(wo, sampler::get_2d());
+                let wi = microfacet::reflect(wo, h);
+                // reflected below the surface, which the shadowing term accounts for
+                if wi[2] <= 0. {
+                    return Scatter::Absorbed;
+                }
 
-                Scatter::Scattered(Ray::new(hit_point, direction), &self.color_kind)
+                let cos = microfacet::dot(wo, h);
+                Scatter::Scattered(
+                    Ray::new(origin, frame.to_world(wi)),
+                    color
+                        * conductor_fresnel(cos, eta, k)
+                        * (distribution.g2(wo, wi) / distribution.g1(wo)),
+                    Some(distribution.visible_normal_pdf(wo, h) / (4. * cos)),
+                )
             }
-            MaterialKind::Light => Scatter::Light(&self.color_kind),
+            MaterialKind::Principled(ref principled) => {
+                // relative to the medium around it
+                let refractive_index = principled
+                    .refractive_index
+                    .at(wavelength, texture_coordinates)
+                    / outside_refractive_index;
+                let (eta, normal) = relative_refractive_index(ray, normal, refractive_index);
+                let frame = Frame::new(normal);
+                let sample = principled
+                    .at(color, texture_coordinates)
+                    .sample(eta, frame.to_local(-ray.direction));
+
+                scattered_locally((hit_point, normal), &frame, sample)
+            }
+            MaterialKind::Light => Scatter::Light(color),
         }
     }
 }
@@ -104,35 +321,317 @@ impl HasIndexer for Material {
     type IndexerType = usize;
 }
 
-pub enum Scatter<'a> {
+/// The pdf per solid angle of a fuzzy metal scattering into `direction`.
+///
+/// The scattered directions point at a uniformly sampled sphere of radius `fuzziness` around the tip of `reflection`,
+/// so every direction crossing that sphere gets the area density converted to solid angle at both crossings.
+fn fuzzy_reflection_pdf(
+    reflection: NormalizedVector3,
+    direction: NormalizedVector3,
+    fuzziness: f32,
+) -> f32 {
+    // the distances t along `direction` with |t * direction - reflection| = fuzziness
+    let cos = direction.dot(reflection);
+    let discriminant = cos * cos - 1. + fuzziness * fuzziness;
+    if discriminant <= 0. {
+        return 0.;
+    }
+    let root = discriminant.sqrt();
+
+    // (t^2 / cos_sphere) / sphere area, with cos_sphere = root / fuzziness
+    [cos - root, cos + root]
+        .into_iter()
+        .filter(|&t| t > 0.)
+        .map(|t| t * t / (2. * TAU * fuzziness * root))
+        .sum()
+}
+
+/// The normal on the side the ray came from
+fn facing(ray: &Ray, normal: NormalizedVector3) -> NormalizedVector3 {
+    if ray.direction.dot(normal) > 0. {
+        -normal
+    } else {
+        normal
+    }
+}
+
+/// The refractive index of the side the ray goes into relative to the side it came from, and the normal facing the ray.
+/// `refractive_index` is the one of the inside relative to the outside.
+fn relative_refractive_index(
+    ray: &Ray,
+    normal: NormalizedVector3,
+    refractive_index: f32,
+) -> (f32, NormalizedVector3) {
+    // If it enters or exits the shape
+    if ray.direction.dot(normal) < 0. {
+        (refractive_index, normal)
+    } else {
+        (1. / refractive_index, -normal)
+    }
+}
+
+/// The ray scattered from a dielectric, starting on the side of the surface it goes to.
+/// Otherwise rays transmitted or reflected at grazing angles could hit the same surface again, crossing it twice.
+fn dielectric_ray(
+    hit_point: Point3,
+    normal: NormalizedVector3,
+    direction: NormalizedVector3,
+) -> Ray {
+    let offset = if direction.dot(normal) > 0. {
+        normal
+    } else {
+        -normal
+    };
+
+    Ray::new(hit_point + offset.to_vector() * 1e-4, direction)
+}
+
+/// The ray scattered into a direction sampled in the local `frame` of a dielectric, or absorbed without a sample.
+/// The sample is the direction, the bsdf times the cosine divided by the pdf, and the pdf.
+fn scattered_locally(
+    (hit_point, normal): (Point3, NormalizedVector3),
+    frame: &Frame,
+    sample: Option<([f32; 3], Color<3, f32>, f32)>,
+) -> Scatter {
+    sample.map_or(Scatter::Absorbed, |(direction, weight, pdf)| {
+        Scatter::Scattered(
+            dielectric_ray(hit_point, normal, frame.to_world(direction)),
+            weight,
+            Some(pdf),
+        )
+    })
+}
+
+/// Reflects or refracts the ray at a smooth dielectric, choosing by the Fresnel term
+fn sample_smooth_dielectric(ray: &Ray, normal: NormalizedVector3, eta: f32) -> NormalizedVector3 {
+    let cos = ray.direction.neg().dot(normal).min(1.);
+    let reflectance = microfacet::fresnel_dielectric(cos, eta);
+
+    // always drawn, so the following dimensions stay aligned
+    let random = sampler::get_1d();
+
+    // always reflects on total internal reflection
+    if random < reflectance {
+        ray.direction.reflect(normal)
+    } else {
+        // refract
+        let perpendicular = (ray.direction + normal * cos) / eta;
+        let discriminant = 1. - (1. - cos * cos) / (eta * eta);
+        let parallel = normal * -discriminant.sqrt();
+
+        NormalizedVector3::new(perpendicular + parallel)
+    }
+}
+
+/// The reflectance of every channel of a conductor
+fn conductor_fresnel(cos: f32, eta: Color<3, f32>, k: Color<3, f32>) -> Color<3, f32> {
+    Color::new(array::from_fn(|channel| {
+        microfacet::fresnel_conductor(cos, eta.inner()[channel], k.inner()[channel])
+    }))
+}
+
+pub enum Scatter {
     Absorbed,
-    Scattered(Ray, &'a ColorKind),
-    Light(&'a ColorKind),
+    /// The scattered ray, the bsdf times the cosine divided by the pdf, and the pdf per solid angle of its direction.
+    /// The pdf is None for single directions, like mirror reflections.
+    Scattered(Ray, Color<3, f32>, Option<f32>),
+    Light(Color<3, f32>),
 }
 
 #[derive(Debug, PartialEq)]
 pub enum MaterialKind {
     Lambertian,
-    Metal { fuzziness: f32 },
-    Glass { refractive_index: f32 },
+    Metal {
+        fuzziness: f32,
+    },
+    /// A rough metal, with the complex refractive index eta + ik per channel.
+    /// Its color tints the reflection, so white keeps the color of the metal.
+    Conductor {
+        distribution: Ggx,
+        eta: Color<3, f32>,
+        k: Color<3, f32>,
+    },
+    /// A smooth or rough dielectric, which reflects and refracts by the exact Fresnel equations.
+    /// Its color tints every scattering, while the absorption tints the light by the distance it travels inside.
+    Glass {
+        refractive_index: RefractiveIndex,
+        distribution: Ggx,
+        /// The fraction of light absorbed per unit distance, for every channel
+        absorption: Option<Color<3, f32>>,
+        /// Where its inside overlaps other glasses, the one with the highest priority fills the overlap
+        priority: u32,
+    },
+    /// A mix of a diffuse base, a metal and a glass, with sheen and a clearcoat, whose color is the base color
+    Principled(Box<Principled>),
     Light,
 }
-#[expect(clippy::fallible_impl_from)] // TODO: Remove once we care about crashes
-impl From<&str> for MaterialKind {
-    fn from(value: &str) -> Self {
-        let mut split = value.split_whitespace();
-        let kind = split.next().unwrap();
+impl MaterialKind {
+    /// The refractive indices of some metals at the red, green and blue wavelengths, as eta and k
+    const CONDUCTORS: [(&str, [f32; 3], [f32; 3]); 3] = [
+        ("gold", [0.143, 0.374, 1.442], [3.983, 2.385, 1.603]),
+        ("copper", [0.200, 0.924, 1.102], [3.912, 2.452, 2.142]),
+        ("aluminium", [1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+    ];
+
+    /// Parses a conductor after its kind, `gold|copper|aluminium` or `eta_r eta_g eta_b k_r k_g k_b`,
+    /// followed by its roughness and optionally a different roughness along the bitangent
+    fn conductor(split: &mut SplitWhitespace, value: &str) -> Result<Self, ParseError> {
+        const EXPECTED: &str = "conductor followed by gold, copper, aluminium or the eta and k of red, green and blue, and its roughness";
+
+        let error = || ParseError::new(EXPECTED, value);
+        let number = |number: Option<&str>| {
+            number
+                .and_then(|number| number.parse().ok())
+                .filter(|&number: &f32| number >= 0.)
+                .ok_or_else(error)
+        };
+
+        let first = split.next();
+        let (eta, k) = if let Some((_, eta, k)) = Self::CONDUCTORS
+            .into_iter()
+            .find(|&(name, ..)| Some(name) == first)
+        {
+            (eta, k)
+        } else {
+            let mut values = [number(first)?, 0., 0., 0., 0., 0.];
+            for value in &mut values[1..] {
+                *value = number(split.next())?;
+            }
+            let [eta_r, eta_g, eta_b, k_r, k_g, k_b] = values;
+            ([eta_r, eta_g, eta_b], [k_r, k_g, k_b])
+        };
+
+        Ok(Self::Conductor {
+            distribution: Self::distribution(split, value, EXPECTED, None)?,
+            eta: Color::new(eta),
+            k: Color::new(k),
+        })
+    }
+    /// Parses a glass after its kind, its refractive index followed by an optional roughness like a conductor's.
+    /// The refractive index is a number, `bk7|silica|diamond`, `cauchy a b` or `sellmeier b1 b2 b3 c1 c2 c3`.
+    /// Optionally followed by `absorption` with the color white light turns after a distance and that distance,
+    /// and by `priority` with the priority of its medium where it overlaps others.
+    fn glass(split: &mut SplitWhitespace, value: &str) -> Result<Self, ParseError> {
+        const EXPECTED: &str = "glass followed by its refractive index, and optionally its roughness, absorption and priority";
+        const KEYWORDS: [&str; 2] = ["absorption", "priority"];
+
+        let refractive_index = RefractiveIndex::parse(split, value, EXPECTED)?;
+
+        let mut split = split.peekable();
+
+        // the roughness ends at the first keyword
+        let mut surface =
+            iter::from_fn(|| split.next_if(|parameter| !KEYWORDS.contains(parameter)));
+        let distribution = Self::distribution(&mut surface, value, EXPECTED, Some(0.))?;
+        if surface.next().is_some() {
+            return Err(ParseError::new(EXPECTED, value));
+        }
 
-        match kind {
-            "lambertian" => Self::Lambertian,
-            "metal" => Self::Metal {
-                fuzziness: split.next().unwrap().parse().unwrap(),
-            },
-            "glass" => Self::Glass {
-                refractive_index: split.next().unwrap().parse().unwrap(),
+        let mut absorption = None;
+        let mut priority = 0;
+        while let Some(keyword) = split.next_if(|parameter| KEYWORDS.contains(parameter)) {
+            if keyword == "absorption" {
+                absorption = Some(Self::absorption(&mut split, value)?);
+            } else {
+                priority = split
+                    .next()
+                    .and_then(|priority| priority.parse().ok())
+                    .ok_or_else(|| ParseError::new("priority followed by an integer", value))?;
+            }
+        }
+
+        Ok(Self::Glass {
+            refractive_index,
+            distribution,
+            absorption,
+            priority,
+        })
+    }
+    /// Parses the color white light turns after traveling a distance through a medium, and that distance,
+    /// into the absorption coefficients of the medium
+    fn absorption<'a>(
+        split: &mut impl Iterator<Item = &'a str>,
+        value: &str,
+    ) -> Result<Color<3, f32>, ParseError> {
+        const EXPECTED: &str = "absorption followed by a color from above 0 to 1 and the distance at which white light turns into it";
+
+        let mut number = || {
+            split
+                .next()
+                .and_then(|number| number.parse().ok())
+                .filter(|&number: &f32| number > 0.)
+                .ok_or_else(|| ParseError::new(EXPECTED, value))
+        };
+
+        let color = [number()?, number()?, number()?];
+        if color.iter().any(|&channel| channel > 1.) {
+            return Err(ParseError::new(EXPECTED, value));
+        }
+        let distance = number()?;
+
+        // Beer-Lambert: color = exp(-absorption * distance)
+        Ok(Color::new(color.map(|channel| -channel.ln() / distance)))
+    }
+    /// Parses a roughness, or takes `default`, and optionally a different roughness along the bitangent
+    fn distribution<'a>(
+        split: &mut impl Iterator<Item = &'a str>,
+        value: &str,
+        expected: &'static str,
+        default: Option<f32>,
+    ) -> Result<Ggx, ParseError> {
+        let number = |number: &str| {
+            number
+                .parse()
+                .ok()
+                .filter(|&number: &f32| number >= 0.)
+                .ok_or_else(|| ParseError::new(expected, value))
+        };
+
+        let roughness = match (split.next(), default) {
+            (Some(roughness), _) => number(roughness)?,
+            (None, Some(default)) => default,
+            (None, None) => return Err(ParseError::new(expected, value)),
+        };
+        let roughness_y = split.next().map_or(Ok(roughness), number)?;
+
+        Ok(Ggx::new([roughness, roughness_y]))
+    }
+}
+impl TryFrom<&str> for MaterialKind {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut split = value.split_whitespace();
+        let kind = split.next();
+
+        let mut parameter = |expected| {
+            split
+                .next()
+                .and_then(|parameter| parameter.parse().ok())
+                .ok_or_else(|| ParseError::new(expected, value))
+        };
+
+        let kind = match kind {
+            Some("lambertian") => Self::Lambertian,
+            Some("metal") => Self::Metal {
+                fuzziness: parameter("metal followed by its fuzziness")?,
             },
-            "light" => Self::Light,
-            other => panic!("Unknown material: {other}"),
+            Some("conductor") => Self::conductor(&mut split, value)?,
+            Some("glass") => Self::glass(&mut split, value)?,
+            Some("principled") => Self::Principled(Box::new(Principled::parse(&mut split, value)?)),
+            Some("light") => Self::Light,
+            _ => {
+                return Err(ParseError::new(
+                    "one of lambertian, metal, conductor, glass, principled or light",
+                    value,
+                ));
+            }
+        };
+
+        // no trailing parameters
+        match split.next() {
+            None => Ok(kind),
+            Some(_) => Err(ParseError::new("no further material parameters", value)),
         }
     }
 }
@@ -147,29 +646,41 @@ pub enum ColorKind {
     },
 }
 impl ColorKind {
-    pub fn texture_from_ppm_p6(file: &str) -> Self {
-        let contents = fs::read(file).unwrap();
+    /// # Errors
+    /// When the file can't be read or isn't a valid ppm p6 file
+    pub fn texture_from_ppm_p6(file: &str) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
 
-        assert_eq!(&contents[0..2], b"P6");
+        let contents = fs::read(file)?;
+
+        if !contents.starts_with(b"P6") {
+            return Err(invalid("not a binary (P6) ppm file"));
+        }
 
         let mut base = 3;
-        let [width, height] = [b' ', b'\n'].map(|pat| {
-            let length = contents[base..]
+        let [Some(width), Some(height)] = [b' ', b'\n'].map(|pat| {
+            let length = contents
+                .get(base..)
+                .unwrap_or_default()
                 .iter()
                 .take_while(|&&byte| byte != pat)
                 .count();
 
-            let num = str::from_utf8(&contents[base..base + length])
-                .unwrap()
-                .parse()
-                .unwrap();
+            let num = contents
+                .get(base..base + length)
+                .and_then(|bytes| str::from_utf8(bytes).ok())
+                .and_then(|num| num.parse::<u32>().ok());
 
             base += length + 1;
 
             num
-        });
+        }) else {
+            return Err(invalid("invalid ppm dimensions"));
+        };
 
-        assert_eq!(&contents[base..base + 3], b"255");
+        if contents.get(base..base + 3) != Some(b"255") {
+            return Err(invalid("only a maximum color value of 255 is supported"));
+        }
 
         // could be done with reinterpretation, but this is not performance critical
         let data: Box<[Pixel]> = contents
@@ -179,12 +690,30 @@ impl ColorKind {
             .map(Color::new)
             .collect();
 
-        assert_eq!(data.len(), width as usize * height as usize);
+        if data.len() != width as usize * height as usize {
+            return Err(invalid("pixel data does not match the ppm dimensions"));
+        }
 
-        Self::Texture {
+        Ok(Self::Texture {
             width,
             height,
             data,
+        })
+    }
+    /// Identifies the pixels of a texture, solid colors are part of the scene description
+    fn hash(&self) -> u64 {
+        match *self {
+            Self::Solid(_) => 0,
+            Self::Texture {
+                width,
+                height,
+                ref data,
+            } => rng::hash(
+                [width, height].map(u64::from).into_iter().chain(
+                    data.iter()
+                        .map(|pixel| rng::hash(pixel.inner().map(u64::from))),
+                ),
+            ),
         }
     }
     /// x & y: 0..=1
@@ -238,3 +767,14 @@ impl ColorKind {
         }
     }
 }
+impl TryFrom<&str> for ColorKind {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        // three numbers, or the path of a ppm texture
+        Color::try_from(value).map(Self::Solid).or_else(|_| {
+            Self::texture_from_ppm_p6(value)
+                .map_err(|_| ParseError::new("a color or the path of a ppm texture", value))
+        })
+    }
+}
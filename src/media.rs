@@ -0,0 +1,160 @@
+use crate::{spectrum::RefractiveIndex, vec3::Color};
+
+/// The inside of a dielectric, which a path enters and leaves through its surface
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Medium {
+    pub refractive_index: RefractiveIndex,
+    /// The fraction of light absorbed per unit distance, for every channel
+    pub absorption: Option<Color<3, f32>>,
+    /// Where media overlap, the one with the highest priority fills the overlap
+    pub priority: u32,
+}
+
+/// The media a path is inside, in the order it entered them, so that nested and overlapping dielectrics
+/// see the right refractive index on their other side (Schmidt & Budge 2002).
+///
+/// The surfaces of a medium inside one with a higher priority are passed through, so a liquid can overlap its glass.
+/// Paths more deeply nested than the stack holds don't remember the innermost media.
+#[derive(Clone, Debug, Default)]
+pub struct Media {
+    stack: [Option<Medium>; 8],
+}
+impl Media {
+    /// The medium the path is in, the entered one with the highest priority
+    pub fn current(&self) -> Option<Medium> {
+        Self::highest(self.stack.iter().flatten())
+    }
+    /// If the surface of `medium` lies inside a medium with a higher priority, so it isn't there
+    pub fn is_skipped(&self, medium: Medium, entering: bool) -> bool {
+        self.outside(medium, entering)
+            .is_some_and(|outside| outside.priority > medium.priority)
+    }
+    /// The refractive index on the other side of the surface of `medium`, 1 for air
+    pub fn outside_refractive_index(&self, medium: Medium, entering: bool) -> RefractiveIndex {
+        self.outside(medium, entering)
+            .map_or(RefractiveIndex::Constant(1.), |outside| {
+                outside.refractive_index
+            })
+    }
+    /// Enters or leaves `medium` through its surface
+    pub fn cross(&mut self, medium: Medium, entering: bool) {
+        if entering {
+            if let Some(free) = self.stack.iter_mut().find(|slot| slot.is_none()) {
+                *free = Some(medium);
+            }
+        } else if let Some(index) = self.last_index(medium) {
+            self.stack[index..].rotate_left(1);
+            if let Some(last) = self.stack.last_mut() {
+                *last = None;
+            }
+        }
+    }
+
+    /// The medium around `medium`, the current one when entering it, or the one it leaves into
+    fn outside(&self, medium: Medium, entering: bool) -> Option<Medium> {
+        let skip = if entering {
+            None
+        } else {
+            self.last_index(medium)
+        };
+
+        Self::highest(
+            self.stack
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| Some(index) != skip)
+                .filter_map(|(_, medium)| medium.as_ref()),
+        )
+    }
+    fn last_index(&self, medium: Medium) -> Option<usize> {
+        self.stack
+            .iter()
+            .rposition(|&entered| entered == Some(medium))
+    }
+    /// The medium with the highest priority, or the last entered one of equal priorities
+    fn highest<'a>(media: impl Iterator<Item = &'a Medium>) -> Option<Medium> {
+        media.max_by_key(|medium| medium.priority).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn medium(refractive_index: f32, priority: u32) -> Medium {
+        Medium {
+            refractive_index: RefractiveIndex::Constant(refractive_index),
+            absorption: None,
+            priority,
+        }
+    }
+
+    #[test]
+    fn nested() {
+        let [glass, water] = [medium(1.5, 0), medium(1.33, 0)];
+        let mut media = Media::default();
+        assert_eq!(media.current(), None);
+        assert_eq!(
+            media.outside_refractive_index(glass, true),
+            RefractiveIndex::Constant(1.)
+        );
+
+        media.cross(glass, true);
+        assert_eq!(media.current(), Some(glass));
+        assert_eq!(
+            media.outside_refractive_index(water, true),
+            RefractiveIndex::Constant(1.5)
+        );
+
+        media.cross(water, true);
+        assert_eq!(media.current(), Some(water));
+        assert_eq!(
+            media.outside_refractive_index(water, false),
+            RefractiveIndex::Constant(1.5)
+        );
+
+        media.cross(water, false);
+        assert_eq!(media.current(), Some(glass));
+        media.cross(glass, false);
+        assert_eq!(media.current(), None);
+    }
+
+    #[test]
+    fn overlapping() {
+        // a liquid overlapping the glass it is in
+        let [glass, liquid] = [medium(1.5, 2), medium(1.33, 1)];
+        let mut media = Media::default();
+
+        media.cross(glass, true);
+        assert!(media.is_skipped(liquid, true));
+        media.cross(liquid, true);
+        assert_eq!(media.current(), Some(glass));
+
+        // leaving the glass into the liquid
+        assert!(!media.is_skipped(glass, false));
+        assert_eq!(
+            media.outside_refractive_index(glass, false),
+            RefractiveIndex::Constant(1.33)
+        );
+        media.cross(glass, false);
+        assert_eq!(media.current(), Some(liquid));
+        assert!(!media.is_skipped(liquid, false));
+    }
+
+    #[test]
+    fn deeper_than_the_stack() {
+        let mut media = Media::default();
+        #[expect(clippy::cast_precision_loss)]
+        let shells: Vec<_> = (0..10).map(|i| medium(1. + i as f32 / 10., 0)).collect();
+        for &shell in &shells {
+            media.cross(shell, true);
+        }
+        // the innermost ones aren't remembered
+        assert_eq!(media.current(), Some(shells[7]));
+
+        for &shell in shells.iter().rev() {
+            media.cross(shell, false);
+        }
+        assert_eq!(media.current(), None);
+    }
+}
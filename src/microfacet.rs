@@ -0,0 +1,409 @@
+use std::{
+    array,
+    f32::consts::{PI, TAU},
+    ops::Neg as _,
+};
+
+use crate::{sampler, vec3::NormalizedVector3};
+
+/// The anisotropic GGX (Trowbridge-Reitz) distribution of microfacet normals, with the Smith shadowing of its heights.
+///
+/// Its directions are in the local frame of the surface, with the normal along z.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ggx {
+    /// The width of the distribution along x and y
+    alpha: [f32; 2],
+}
+impl Ggx {
+    /// Squares the roughness, so it changes the appearance about linearly
+    pub fn new(roughness: [f32; 2]) -> Self {
+        Self {
+            alpha: roughness.map(|roughness| (roughness * roughness).max(1e-4)),
+        }
+    }
+    /// If the surface is so smooth, that it is better treated as a perfect mirror
+    pub fn is_smooth(self) -> bool {
+        self.alpha[0].max(self.alpha[1]) < 1e-3
+    }
+
+    /// The density of microfacet normals `h`, per projected solid angle
+    pub fn d(self, [x, y, z]: [f32; 3]) -> f32 {
+        let [alpha_x, alpha_y] = self.alpha;
+        let denominator = (x / alpha_x).powi(2) + (y / alpha_y).powi(2) + z * z;
+
+        1. / (PI * alpha_x * alpha_y * denominator * denominator)
+    }
+    /// The ratio of hidden to visible microfacet area seen from `w`
+    fn lambda(self, [x, y, z]: [f32; 3]) -> f32 {
+        let [alpha_x, alpha_y] = self.alpha;
+        let tan_squared = ((alpha_x * x).powi(2) + (alpha_y * y).powi(2)) / (z * z);
+
+        ((1. + tan_squared).sqrt() - 1.) / 2.
+    }
+    /// The fraction of microfacets that are visible from `w`
+    pub fn g1(self, w: [f32; 3]) -> f32 {
+        1. / (1. + self.lambda(w))
+    }
+    /// The fraction of microfacets that are visible from both `wo` and `wi`, with correlated heights
+    pub fn g2(self, wo: [f32; 3], wi: [f32; 3]) -> f32 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal proportionally to its visible area from `wo` (Heitz 2018)
+    pub fn sample_visible_normal(self, wo: [f32; 3], [u, v]: [f32; 2]) -> [f32; 3] {
+        let [alpha_x, alpha_y] = self.alpha;
+
+        // stretch the view, so the distribution becomes a hemisphere
+        let view = normalize([alpha_x * wo[0], alpha_y * wo[1], wo[2]]);
+
+        let length_squared = view[0] * view[0] + view[1] * view[1];
+        let tangent = if length_squared > 0. {
+            let length = length_squared.sqrt();
+            [-view[1] / length, view[0] / length, 0.]
+        } else {
+            [1., 0., 0.]
+        };
+        let bitangent = cross(view, tangent);
+
+        // a point on the projected hemisphere, squashed towards the visible half
+        let radius = u.sqrt();
+        let angle = TAU * v;
+        let t1 = radius * angle.cos();
+        let t2 = radius * angle.sin();
+        let blend = 0.5 * (1. + view[2]);
+        let t2 = (1. - blend) * (1. - t1 * t1).sqrt() + blend * t2;
+        let t3 = (1. - t1 * t1 - t2 * t2).max(0.).sqrt();
+
+        let normal: [f32; 3] =
+            array::from_fn(|i| t1 * tangent[i] + t2 * bitangent[i] + t3 * view[i]);
+
+        // unstretch
+        normalize([
+            alpha_x * normal[0],
+            alpha_y * normal[1],
+            normal[2].max(1e-6),
+        ])
+    }
+    /// The pdf of `sample_visible_normal` returning `h` for `wo`
+    pub fn visible_normal_pdf(self, wo: [f32; 3], h: [f32; 3]) -> f32 {
+        self.g1(wo) * dot(wo, h).max(0.) * self.d(h) / wo[2]
+    }
+}
+
+/// The reflectance of a dielectric for unpolarized light arriving at `cos` to the normal,
+/// with `eta` the refractive index of the far side relative to the near side
+pub fn fresnel_dielectric(cos: f32, eta: f32) -> f32 {
+    let cos = cos.clamp(0., 1.);
+    let sin_squared = (1. - cos * cos) / (eta * eta);
+    // total internal reflection
+    if sin_squared >= 1. {
+        return 1.;
+    }
+    let cos_transmitted = (1. - sin_squared).sqrt();
+
+    let parallel = (eta * cos - cos_transmitted) / (eta * cos + cos_transmitted);
+    let perpendicular = (cos - eta * cos_transmitted) / (cos + eta * cos_transmitted);
+
+    (parallel * parallel).midpoint(perpendicular * perpendicular)
+}
+
+/// The reflectance of a conductor with the complex refractive index eta + ik, for unpolarized light
+pub fn fresnel_conductor(cos: f32, eta: f32, k: f32) -> f32 {
+    let cos = cos.clamp(0., 1.);
+    let cos_squared = cos * cos;
+    let sin_squared = 1. - cos_squared;
+
+    let t0 = eta * eta - k * k - sin_squared;
+    let a_squared_plus_b_squared = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a_squared_plus_b_squared + t0)).max(0.).sqrt();
+
+    // perpendicular
+    let t1 = a_squared_plus_b_squared + cos_squared;
+    let t2 = 2. * a * cos;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+
+    // parallel
+    let t3 = cos_squared * a_squared_plus_b_squared + sin_squared.powi(2);
+    let t4 = t2 * sin_squared;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+
+    perpendicular.midpoint(parallel)
+}
+
+/// The directions perpendicular to a normal, converting between world space and its local frame
+pub struct Frame {
+    tangent: NormalizedVector3,
+    bitangent: NormalizedVector3,
+    normal: NormalizedVector3,
+}
+impl Frame {
+    pub fn new(normal: NormalizedVector3) -> Self {
+        let [tangent, bitangent] = sampler::orthonormal_basis(normal);
+
+        Self {
+            tangent,
+            bitangent,
+            normal,
+        }
+    }
+    pub fn to_local(&self, direction: NormalizedVector3) -> [f32; 3] {
+        [
+            direction.dot(self.tangent),
+            direction.dot(self.bitangent),
+            direction.dot(self.normal),
+        ]
+    }
+    pub fn to_world(&self, [x, y, z]: [f32; 3]) -> NormalizedVector3 {
+        (self.tangent * x + self.bitangent.to_vector() * y + self.normal.to_vector() * z)
+            .normalize()
+    }
+}
+
+/// Mirrors `w` at the microfacet normal `h`
+pub fn reflect(w: [f32; 3], h: [f32; 3]) -> [f32; 3] {
+    let cos = dot(w, h);
+    array::from_fn(|i| 2. * cos * h[i] - w[i])
+}
+
+/// Refracts `w` through the microfacet normal `h` on the same side, into a medium with the relative refractive index `eta`.
+/// None for total internal reflection.
+pub fn refract(w: [f32; 3], h: [f32; 3], eta: f32) -> Option<[f32; 3]> {
+    let cos = dot(w, h);
+    let sin_squared = (1. - cos * cos) / (eta * eta);
+    if sin_squared >= 1. {
+        return None;
+    }
+    let cos_transmitted = (1. - sin_squared).sqrt();
+
+    Some(array::from_fn(|i| {
+        -w[i] / eta + (cos / eta - cos_transmitted) * h[i]
+    }))
+}
+
+/// Samples a rough dielectric in the local frame facing `wo`, choosing between reflection and transmission by the Fresnel term.
+///
+/// Returns the direction, the bsdf times the cosine divided by the pdf, and the pdf.
+///
+/// Like the smooth glass, it leaves out the scaling of the radiance by the squared ratio of the refractive indices,
+/// which cancels out once a path leaves the shape again.
+pub fn sample_rough_dielectric(
+    distribution: Ggx,
+    eta: f32,
+    wo: [f32; 3],
+) -> Option<([f32; 3], f32, f32)> {
+    // always drawn, so the following dimensions stay aligned
+    let sample = sampler::get_2d();
+    let random = sampler::get_1d();
+    sample_rough_dielectric_with(distribution, eta, wo, sample, random)
+}
+
+/// `sample_rough_dielectric` with the sample of the microfacet normal and the random number choosing reflection given,
+/// for callers that draw them along with other choices
+pub fn sample_rough_dielectric_with(
+    distribution: Ggx,
+    eta: f32,
+    wo: [f32; 3],
+    sample: [f32; 2],
+    random: f32,
+) -> Option<([f32; 3], f32, f32)> {
+    if wo[2] <= 0. {
+        return None;
+    }
+    let h = distribution.sample_visible_normal(wo, sample);
+
+    let cos = dot(wo, h);
+    let reflectance = fresnel_dielectric(cos, eta);
+    let normal_pdf = distribution.visible_normal_pdf(wo, h);
+
+    // scattering to the wrong side of the surface is absorbed, which the shadowing term accounts for
+    let (wi, pdf) = if random < reflectance {
+        let wi = reflect(wo, h);
+        if wi[2] <= 0. {
+            return None;
+        }
+        (wi, reflectance * normal_pdf / (4. * cos))
+    } else {
+        let wi = refract(wo, h, eta)?;
+        if wi[2] >= 0. {
+            return None;
+        }
+        let cos_transmitted = dot(wi, h);
+        (
+            wi,
+            (1. - reflectance) * normal_pdf * -cos_transmitted
+                / (cos_transmitted + cos / eta).powi(2),
+        )
+    };
+
+    Some((wi, distribution.g2(wo, wi) / distribution.g1(wo), pdf))
+}
+
+/// The bsdf times the cosine and the pdf of `sample_rough_dielectric` for the local directions `wo` and `wi`
+pub fn evaluate_rough_dielectric(
+    distribution: Ggx,
+    eta: f32,
+    wo: [f32; 3],
+    wi: [f32; 3],
+) -> (f32, f32) {
+    if wo[2] <= 0. || wi[2] == 0. {
+        return (0., 0.);
+    }
+
+    // reflection
+    if wi[2] > 0. {
+        let h = normalize(array::from_fn(|i| wo[i] + wi[i]));
+        let reflectance = fresnel_dielectric(dot(wo, h), eta);
+        let d = distribution.d(h);
+
+        return (
+            reflectance * d * distribution.g2(wo, wi) / (4. * wo[2]),
+            reflectance * distribution.g1(wo) * d / (4. * wo[2]),
+        );
+    }
+
+    // transmission, through the microfacet normal that refracts `wo` into `wi`
+    let h = normalize(array::from_fn(|i| wo[i] + wi[i] * eta));
+    let h = if h[2] < 0. { h.map(f32::neg) } else { h };
+    let cos = dot(wo, h);
+    let cos_transmitted = dot(wi, h);
+    if cos <= 0. || cos_transmitted >= 0. {
+        return (0., 0.);
+    }
+
+    let transmittance = 1. - fresnel_dielectric(cos, eta);
+    let d = distribution.d(h);
+    // the change from microfacet normals to transmitted directions
+    let jacobian = -cos_transmitted / (cos_transmitted + cos / eta).powi(2);
+
+    (
+        transmittance * d * distribution.g2(wo, wi) * cos * jacobian / wo[2],
+        transmittance * distribution.g1(wo) * d * cos * jacobian / wo[2],
+    )
+}
+
+pub fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn normalize(w: [f32; 3]) -> [f32; 3] {
+    let length = dot(w, w).sqrt();
+    w.map(|e| e / length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Integrates `f` over the directions of the upper hemisphere
+    #[expect(clippy::cast_precision_loss)]
+    fn integrate_hemisphere(f: impl Fn([f32; 3]) -> f32) -> f32 {
+        const STEPS: [u32; 2] = [2000, 400];
+        let [theta_step, phi_step] = [PI / 2. / STEPS[0] as f32, TAU / STEPS[1] as f32];
+
+        (0..STEPS[0])
+            .map(|i| {
+                let theta = (i as f32 + 0.5) * theta_step;
+                let (sin, cos) = theta.sin_cos();
+                (0..STEPS[1])
+                    .map(|j| {
+                        let phi = (j as f32 + 0.5) * phi_step;
+                        f([sin * phi.cos(), sin * phi.sin(), cos]) * sin
+                    })
+                    .sum::<f32>()
+                    * theta_step
+                    * phi_step
+            })
+            .sum()
+    }
+
+    #[test]
+    fn fresnel_dielectric_reference_values() {
+        // ((1 - 1.5) / (1 + 1.5))²
+        assert!((fresnel_dielectric(1., 1.5) - 0.04).abs() < 1e-6);
+        // at brewster's angle only the perpendicular polarization is reflected
+        let brewster = 1. / 1.5_f32.hypot(1.);
+        assert!((fresnel_dielectric(brewster, 1.5) - 0.073_96).abs() < 1e-4);
+        assert!((fresnel_dielectric(0., 1.5) - 1.).abs() < 1e-6);
+        // total internal reflection beyond the critical angle of about 48°
+        assert!((fresnel_dielectric(0.6, 1. / 1.5) - 1.).abs() < 1e-6);
+        assert!(fresnel_dielectric(0.8, 1. / 1.5) < 1.);
+    }
+
+    #[test]
+    fn fresnel_conductor_reference_values() {
+        // ((n - 1)² + k²) / ((n + 1)² + k²) for gold in the red
+        assert!((fresnel_conductor(1., 0.143, 3.983) - 0.966_69).abs() < 1e-4);
+        assert!((fresnel_conductor(0., 0.143, 3.983) - 1.).abs() < 1e-6);
+        // without absorption, it is a dielectric
+        for cos in [1., 0.7, 0.3, 0.1] {
+            assert!(
+                (fresnel_conductor(cos, 1.5, 0.) - fresnel_dielectric(cos, 1.5)).abs() < 1e-5,
+                "{cos}"
+            );
+        }
+    }
+
+    #[test]
+    fn ggx_normalization() {
+        for roughness in [[0.5; 2], [1.; 2], [0.4, 0.8]] {
+            let distribution = Ggx::new(roughness);
+
+            // the projected microfacet area is the one of the surface
+            let projected = integrate_hemisphere(|h| distribution.d(h) * h[2]);
+            assert!((projected - 1.).abs() < 1e-2, "{roughness:?}: {projected}");
+
+            // the visible normals are a distribution
+            for wo in [[0., 0., 1.], [0.6, 0., 0.8], [0., 0.9, 0.1]] {
+                let wo = normalize(wo);
+                let visible = integrate_hemisphere(|h| distribution.visible_normal_pdf(wo, h));
+                assert!(
+                    (visible - 1.).abs() < 1e-2,
+                    "{roughness:?} {wo:?}: {visible}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rough_dielectric_samples_match_evaluation() {
+        let distribution = Ggx::new([0.4; 2]);
+
+        for eta in [1.5, 1. / 1.5] {
+            for wo in [[0., 0., 1.], [0.6, 0., 0.8], [0., 0.95, 0.3]] {
+                let wo = normalize(wo);
+                let mut albedo = 0.;
+                for index in 0..10_000 {
+                    sampler::start_sample(sampler::SamplerKind::Independent, 0, index, 1, 0);
+                    let Some((wi, weight, pdf)) = sample_rough_dielectric(distribution, eta, wo)
+                    else {
+                        continue;
+                    };
+                    albedo += weight;
+
+                    let (value, evaluated_pdf) =
+                        evaluate_rough_dielectric(distribution, eta, wo, wi);
+                    assert!(
+                        (evaluated_pdf / pdf - 1.).abs() < 1e-3,
+                        "{eta} {wo:?} {wi:?}: {evaluated_pdf} {pdf}"
+                    );
+                    assert!(
+                        (value / evaluated_pdf / weight - 1.).abs() < 1e-3,
+                        "{eta} {wo:?} {wi:?}: {value} {weight}"
+                    );
+                }
+
+                // only the light the microfacets shadow is lost
+                albedo /= 10_000.;
+                assert!((0.85..=1.).contains(&albedo), "{eta} {wo:?}: {albedo}");
+            }
+        }
+    }
+}
@@ -1,65 +1,140 @@
-use std::{collections::HashMap, fs};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs, io,
+    path::Path,
+    str::Lines,
+};
 
 use crate::{
-    config::Interner,
+    config::{Interner, ParseError, line_number},
     indices::Indexer,
     material::{ColorKind, Material, MaterialKind},
+    microfacet::Ggx,
+    principled::{Principled, PrincipledRefractiveIndex},
+    rng,
     shapes::{MaterialIndexer, NormalsTextureCoordinates, Triangle},
+    spectrum::RefractiveIndex,
     vec3::{Color, New as _, NormalizedVector3, Point3, Vector3},
 };
 
+#[derive(Debug)]
+pub struct ObjError {
+    /// The obj, mtl or texture file the error occurred in
+    pub path: String,
+    /// The line the error occurred on, if it is tied to one
+    pub line: Option<usize>,
+    pub kind: ObjErrorKind,
+}
+impl Display for ObjError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        match self.kind {
+            ObjErrorKind::Io(ref error) => write!(f, ": {error}"),
+            ObjErrorKind::Parse(ref error) => write!(f, ": {error}"),
+        }
+    }
+}
+impl Error for ObjError {}
+
+#[derive(Debug)]
+pub enum ObjErrorKind {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+/// Parses the obj file at `path`, alongside its mtl file and textures.
+/// Pushes the hashes of the obj and mtl contents to `file_hashes`
+/// # Errors
+/// When a file can't be read or contains invalid data
 #[inline(always)]
+#[expect(clippy::too_many_lines)]
 pub fn parse(
     path: &str,
     materials: &mut Interner<Material>,
     texture_coordinates_out: &mut Vec<[[f32; 2]; 3]>,
     normals_out: &mut Vec<[NormalizedVector3; 3]>,
     barycentric_precomputed: &mut Vec<[f32; 4]>,
-) -> Vec<Triangle> {
-    let string = fs::read_to_string(path).expect("Failed to read obj file");
+    file_hashes: &mut Vec<u64>,
+) -> Result<Vec<Triangle>, ObjError> {
+    let string = fs::read_to_string(path).map_err(|error| ObjError {
+        path: path.to_owned(),
+        line: None,
+        kind: ObjErrorKind::Io(error),
+    })?;
+    file_hashes.push(rng::hash(string.bytes().map(u64::from)));
     let lines = string.lines();
 
-    let parent_path = {
-        let parent_position = path.rfind('/').unwrap();
-        &path[..=parent_position]
+    let error = |line: &str, expected| ObjError {
+        path: path.to_owned(),
+        line: line_number(&string, line),
+        kind: ObjErrorKind::Parse(ParseError::new(expected, line)),
     };
 
-    // Option<(contents, parent_path)>
+    // empty for a file in the current directory
+    let parent_path = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+    // Option<(contents, path)>
     let material_file = lines
         .clone()
-        .find(|line| line.starts_with("mtllib"))
-        .map(|line| {
-            fs::read_to_string(format!("{}/{}", parent_path, &line[7..]))
-                .expect("Failed to read mtl file")
-        });
+        .find_map(|line| Some((line, arguments(line, "mtllib")?)))
+        .map(|(line, file)| {
+            if file.is_empty() {
+                return Err(error(line, "mtllib followed by a file"));
+            }
+            let material_path = parent_path.join(file).display().to_string();
+            fs::read_to_string(&material_path)
+                .map(|contents| (contents, material_path.clone()))
+                .map_err(|error| ObjError {
+                    path: material_path,
+                    line: None,
+                    kind: ObjErrorKind::Io(error),
+                })
+        })
+        .transpose()?;
+    if let Some((ref contents, _)) = material_file {
+        file_hashes.push(rng::hash(contents.bytes().map(u64::from)));
+    }
 
-    let name_index = parse_materials(materials, material_file.as_deref(), parent_path);
+    let name_index = parse_materials(
+        materials,
+        material_file
+            .as_ref()
+            .map(|material_file| (material_file.0.as_str(), material_file.1.as_str())),
+        parent_path,
+    )?;
 
     let vertices: Vec<Point3> = lines
         .clone()
-        .filter(|line| line.starts_with("v "))
-        .map(|line| line[2..].trim().into())
-        .collect();
+        .filter_map(|line| Some((line, arguments(line, "v")?)))
+        .map(|(line, values)| Point3::try_from(values).map_err(|_| error(line, "v x y z")))
+        .collect::<Result<_, _>>()?;
 
     let texture_coordinates: Vec<[f32; 2]> = lines
         .clone()
-        .filter(|line| line.starts_with("vt"))
-        .map(|line| {
-            let mut iter = line[3..].trim().split(' ').map(|str| str.parse().unwrap());
-            let texture_coordinates = [iter.next().unwrap(), iter.next().unwrap()];
-
-            if iter.next().is_some_and(|value| value != 0.) {
+        .filter_map(|line| Some((line, arguments(line, "vt")?)))
+        .map(|(line, values)| {
+            let mut iter = values.split(' ').map(str::parse);
+            let (Some(Ok(u)), Some(Ok(v))) = (iter.next(), iter.next()) else {
+                return Err(error(line, "vt u v"));
+            };
+
+            if iter.next().is_some_and(|value| value != Ok(0.)) {
                 eprintln!("Warning: only 2d texture coordinates are currently supported");
             }
-            texture_coordinates
+            Ok([u, v])
         })
-        .collect();
+        .collect::<Result<_, _>>()?;
 
     let normals: Vec<Vector3> = lines
         .clone()
-        .filter(|line| line.starts_with("vn"))
-        .map(|line| line[3..].trim().into())
-        .collect();
+        .filter_map(|line| Some((line, arguments(line, "vn")?)))
+        .map(|(line, values)| Vector3::try_from(values).map_err(|_| error(line, "vn x y z")))
+        .collect::<Result<_, _>>()?;
 
     let mut triangles = Vec::new();
 
@@ -80,11 +155,12 @@ pub fn parse(
                 },
             );
 
-        lines
-            .filter(|line| line.starts_with('f')) // get faces
-            .for_each(|line| {
-                // get vertices and normals
-                let mut iter = line[1..].split_whitespace().map(|part| {
+        // get faces
+        for line in lines.filter(|line| line.starts_with('f')) {
+            // get vertices and normals
+            let corners = line[1..]
+                .split_whitespace()
+                .map(|part| {
                     // (vertex, texture, normal)
                     let mut indices = part
                         .split('/')
@@ -93,143 +169,502 @@ pub fn parse(
                             let index: isize = str_index.parse().ok()?;
 
                             #[expect(clippy::cast_sign_loss)] // we check for negative index
-                            Some(if index < 0 {
-                                len - index.unsigned_abs()
+                            if index < 0 {
+                                len.checked_sub(index.unsigned_abs())
                             } else {
-                                index as usize - 1
-                            })
+                                (index as usize).checked_sub(1)
+                            }
                         });
-                    (
-                        vertices[indices.next().unwrap().unwrap()],
-                        indices
-                            .next()
-                            .flatten()
-                            .map(|index| texture_coordinates[index]),
-                        indices.next().flatten().map(|index| normals[index]),
-                    )
-                });
-
-                let (vertex1, tc1, normal1) = iter.next().unwrap();
-
-                // Fan triangulation
-                // TODO: maybe use a better approach
-                iter.map_windows(
-                    |&[(vertex2, tc2, normal2), (vertex3, tc3, normal3)]: &[_; 2]| {
-                        // has texture coordinates
-                        let texture_coordinates_index = if let Some(tc1) = tc1
-                            && let Some(tc2) = tc2
-                            && let Some(tc3) = tc3
-                        {
-                            let index = texture_coordinates_out.len();
-                            texture_coordinates_out.push([tc1, tc2, tc3]);
-                            Some(Indexer::new(index.try_into().unwrap()))
-                        } else {
-                            None
-                        };
-                        // has vertex normals
-                        let normals_index = if let Some(normal1) = normal1
-                            && let Some(normal2) = normal2
-                            && let Some(normal3) = normal3
-                        {
-                            let normal_index = normals_out.len();
-
-                            normals_out.push([normal1, normal2, normal3].map(Vector3::normalize));
-
-                            Some(Indexer::new(normal_index.try_into().unwrap()))
-                        } else {
-                            None
-                        };
-                        let mut barycentric_precomputed_index = || {
-                            let e1 = vertex1.vector_to(vertex2);
-                            let e2 = vertex1.vector_to(vertex3);
-
-                            let (d00, d01, d11) = (e1.dot(e1), e1.dot(e2), e2.dot(e2));
-
-                            let index = barycentric_precomputed.len();
-
-                            barycentric_precomputed.push([d00, d01, d11, d00 * d11 - d01.powi(2)]);
+                    let vertex = indices
+                        .next()
+                        .flatten()
+                        .and_then(|index| vertices.get(index).copied());
+                    // missing indices are fine, but existing ones have to be valid
+                    let texture_coordinate = indices.next().flatten().map_or(Some(None), |index| {
+                        texture_coordinates.get(index).copied().map(Some)
+                    });
+                    let normal = indices
+                        .next()
+                        .flatten()
+                        .map_or(Some(None), |index| normals.get(index).copied().map(Some));
+
+                    match (vertex, texture_coordinate, normal) {
+                        (Some(vertex), Some(texture_coordinate), Some(normal)) => {
+                            Ok((vertex, texture_coordinate, normal))
+                        }
+                        _ => Err(error(line, "f with valid v/vt/vn indices")),
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if corners.len() < 3 {
+                return Err(error(line, "f with at least three vertices"));
+            }
 
-                            Indexer::new(index.try_into().unwrap())
-                        };
-                        let normals_texture_coordinates =
-                            match (texture_coordinates_index, normals_index) {
-                                (Some(texture_coordinates_index), Some(normals_index)) => {
-                                    NormalsTextureCoordinates::Both {
-                                        normals_index,
-                                        texture_coordinates_index,
-                                        barycentric_precomputed_index:
-                                            barycentric_precomputed_index(),
-                                    }
-                                }
-                                (Some(texture_coordinates_index), None) => {
-                                    NormalsTextureCoordinates::TextureCoordinates {
-                                        texture_coordinates_index,
-                                        barycentric_precomputed_index:
-                                            barycentric_precomputed_index(),
-                                    }
-                                }
-                                (None, Some(normals_index)) => NormalsTextureCoordinates::Normals {
+            let (vertex1, tc1, normal1) = corners[0];
+            let iter = corners[1..].iter().copied();
+
+            // Fan triangulation
+            // TODO: maybe use a better approach
+            iter.map_windows(
+                |&[(vertex2, tc2, normal2), (vertex3, tc3, normal3)]: &[_; 2]| {
+                    // has texture coordinates
+                    let texture_coordinates_index = if let Some(tc1) = tc1
+                        && let Some(tc2) = tc2
+                        && let Some(tc3) = tc3
+                    {
+                        let index = texture_coordinates_out.len();
+                        texture_coordinates_out.push([tc1, tc2, tc3]);
+                        Some(Indexer::new(index.try_into().unwrap()))
+                    } else {
+                        None
+                    };
+                    // has vertex normals
+                    let normals_index = if let Some(normal1) = normal1
+                        && let Some(normal2) = normal2
+                        && let Some(normal3) = normal3
+                    {
+                        let normal_index = normals_out.len();
+
+                        normals_out.push([normal1, normal2, normal3].map(Vector3::normalize));
+
+                        Some(Indexer::new(normal_index.try_into().unwrap()))
+                    } else {
+                        None
+                    };
+                    let mut barycentric_precomputed_index = || {
+                        let e1 = vertex1.vector_to(vertex2);
+                        let e2 = vertex1.vector_to(vertex3);
+
+                        let (d00, d01, d11) = (e1.dot(e1), e1.dot(e2), e2.dot(e2));
+
+                        let index = barycentric_precomputed.len();
+
+                        barycentric_precomputed.push([d00, d01, d11, d00 * d11 - d01.powi(2)]);
+
+                        Indexer::new(index.try_into().unwrap())
+                    };
+                    let normals_texture_coordinates =
+                        match (texture_coordinates_index, normals_index) {
+                            (Some(texture_coordinates_index), Some(normals_index)) => {
+                                NormalsTextureCoordinates::Both {
                                     normals_index,
+                                    texture_coordinates_index,
+                                    barycentric_precomputed_index: barycentric_precomputed_index(),
+                                }
+                            }
+                            (Some(texture_coordinates_index), None) => {
+                                NormalsTextureCoordinates::TextureCoordinates {
+                                    texture_coordinates_index,
                                     barycentric_precomputed_index: barycentric_precomputed_index(),
-                                },
-                                (None, None) => NormalsTextureCoordinates::None,
-                            };
-
-                        Triangle::new(
-                            vertex1,
-                            vertex2,
-                            vertex3,
-                            normals_texture_coordinates,
-                            material_index,
-                        )
-                    },
-                )
-                .collect_into(&mut triangles);
-            });
-    }
-
-    triangles
+                                }
+                            }
+                            (None, Some(normals_index)) => NormalsTextureCoordinates::Normals {
+                                normals_index,
+                                barycentric_precomputed_index: barycentric_precomputed_index(),
+                            },
+                            (None, None) => NormalsTextureCoordinates::None,
+                        };
+
+                    Triangle::new(
+                        vertex1,
+                        vertex2,
+                        vertex3,
+                        normals_texture_coordinates,
+                        material_index,
+                    )
+                },
+            )
+            .collect_into(&mut triangles);
+        }
+    }
+
+    Ok(triangles)
 }
 
 /// Returns a `HashMap` of (material name -> material index)
 // TODO: parse some more properties
 fn parse_materials<'a>(
     materials: &mut Interner<Material>,
-    material_file: Option<&'a str>,
-    parent_path: &str,
-) -> HashMap<&'a str, MaterialIndexer> {
+    material_file: Option<(&'a str, &str)>,
+    parent_path: &Path,
+) -> Result<HashMap<&'a str, MaterialIndexer>, ObjError> {
     let mut name_index = HashMap::new();
-    if let Some(material_file) = material_file {
+    if let Some((material_file, material_path)) = material_file {
         // skip(1): skip header etc
         for material_section in material_file.split("newmtl ").skip(1) {
             let mut lines = material_section.lines();
 
-            let name = lines.next().unwrap();
+            let name = lines.next().unwrap_or_default();
 
-            let diffuse_color = lines
-                .clone()
-                .find(|line| line.starts_with("Kd"))
-                .map(|line| Color::from(&line[3..]));
+            let index = materials.intern(material(
+                &lines,
+                (material_file, material_path),
+                parent_path,
+            )?);
 
-            let diffuse_texture = lines.find(|line| line.starts_with("map_Kd")).map(|line| {
-                let file_name = &line[7..];
-                ColorKind::texture_from_ppm_p6(&format!("{parent_path}/{file_name}"))
-            });
+            name_index.insert(name, index);
+        }
+    }
 
-            let material = Material::new(
-                MaterialKind::Lambertian,
-                match (diffuse_texture, diffuse_color) {
-                    (Some(diffuse_texture), _) => diffuse_texture,
-                    (None, Some(diffuse_color)) => ColorKind::Solid(diffuse_color),
-                    (None, None) => ColorKind::Solid(Color::new([0.5; 3])),
-                },
+    Ok(name_index)
+}
+
+/// The material of an mtl section, in the order of glass for the transparent materials, see `dielectric`,
+/// then principled for the ones with physically based parameters, then lambertian.
+/// So `Pr` is the roughness of the glass, and only of the principled material if there is no glass.
+fn material(
+    lines: &Lines<'_>,
+    (material_file, material_path): (&str, &str),
+    parent_path: &Path,
+) -> Result<Material, ObjError> {
+    // transparent materials refract, tinted by their transmission filter
+    if let Some(dielectric) = dielectric(lines, material_file, material_path)? {
+        let transmission_color = lines
+            .clone()
+            .find_map(|line| Some((line, arguments(line, "Tf")?)))
+            .map(|(line, color)| {
+                Color::try_from(color).map_err(|error| ObjError {
+                    path: material_path.to_owned(),
+                    line: line_number(material_file, line),
+                    kind: ObjErrorKind::Parse(error),
+                })
+            })
+            .transpose()?;
+
+        return Ok(Material::new(
+            dielectric,
+            ColorKind::Solid(transmission_color.unwrap_or_else(|| Color::new([1.; 3]))),
+        ));
+    }
+
+    let diffuse_color = lines
+        .clone()
+        .find_map(|line| Some((line, arguments(line, "Kd")?)))
+        .map(|(line, color)| {
+            Color::try_from(color).map_err(|error| ObjError {
+                path: material_path.to_owned(),
+                line: line_number(material_file, line),
+                kind: ObjErrorKind::Parse(error),
+            })
+        })
+        .transpose()?;
+    let diffuse_texture = lines
+        .clone()
+        .find_map(|line| Some((line, arguments(line, "map_Kd")?)))
+        .map(|(line, texture)| {
+            let texture_path =
+                texture_path(line, texture, parent_path, (material_file, material_path))?;
+            ColorKind::texture_from_ppm_p6(&texture_path).map_err(|error| ObjError {
+                path: texture_path,
+                line: None,
+                kind: ObjErrorKind::Io(error),
+            })
+        })
+        .transpose()?;
+
+    let diffuse = match (diffuse_texture, diffuse_color) {
+        (Some(diffuse_texture), _) => diffuse_texture,
+        (None, Some(diffuse_color)) => ColorKind::Solid(diffuse_color),
+        (None, None) => ColorKind::Solid(Color::new([0.5; 3])),
+    };
+    Ok(
+        match principled(lines, (material_file, material_path), parent_path)? {
+            Some(principled) => {
+                Material::new(MaterialKind::Principled(Box::new(principled)), diffuse)
+            }
+            None => Material::new(MaterialKind::Lambertian, diffuse),
+        },
+    )
+}
+
+/// A glass for the materials that refract by their illumination model,
+/// or that are transparent by their dissolve or transparency and have a refractive index.
+/// Transparency alone is the cutout of a texture, which is left opaque.
+///
+/// Its refractive index is `Ni` and its roughness is `Pr`,
+/// or the roughness of the Beckmann distribution matching the Phong exponent `Ns`.
+fn dielectric(
+    lines: &Lines<'_>,
+    material_file: &str,
+    material_path: &str,
+) -> Result<Option<MaterialKind>, ObjError> {
+    let number = |name: &str| number(lines, name, (material_file, material_path));
+
+    // the models with refraction
+    let refracts = number("illum")?.is_some_and(|illum| [4., 6., 7., 9.].contains(&illum));
+    let transparent = number("d")?.is_some_and(|dissolve| dissolve < 1.)
+        || number("Tr")?.is_some_and(|transparency| transparency > 0.);
+    if !refracts && (!transparent || number("Ni")?.is_none()) {
+        return Ok(None);
+    }
+
+    let roughness = match (number("Pr")?, number("Ns")?) {
+        (Some(roughness), _) => roughness,
+        (None, Some(exponent)) => (2. / (exponent.max(0.) + 2.)).sqrt().sqrt(),
+        (None, None) => 0.,
+    };
+
+    Ok(Some(MaterialKind::Glass {
+        refractive_index: RefractiveIndex::Constant(
+            number("Ni")?.filter(|&index| index > 0.).unwrap_or(1.5),
+        ),
+        distribution: Ggx::new([roughness.max(0.); 2]),
+        absorption: None,
+        priority: 0,
+    }))
+}
+
+/// A principled material for the opaque materials with the physically based parameters
+/// `Pm` for metallic, `Pr` for roughness, `Ps` for sheen, `Pc` for clearcoat and `Pcr` for clearcoat roughness,
+/// or their textures like `map_Pm`. Its refractive index is `Ni`, or the texture `map_Ni` mapped from 1 to 3.
+fn principled(
+    lines: &Lines<'_>,
+    (material_file, material_path): (&str, &str),
+    parent_path: &Path,
+) -> Result<Option<Principled>, ObjError> {
+    let texture = |name: &str| {
+        lines
+            .clone()
+            .find_map(|line| Some((line, arguments(line.strip_prefix("map_")?, name)?)))
+            .map(|(line, texture)| {
+                let texture_path =
+                    texture_path(line, texture, parent_path, (material_file, material_path))?;
+                ColorKind::texture_from_ppm_p6(&texture_path).map_err(|error| ObjError {
+                    path: texture_path,
+                    line: None,
+                    kind: ObjErrorKind::Io(error),
+                })
+            })
+            .transpose()
+    };
+    let parameter = |name: &str| {
+        // a texture overrides the number
+        if let Some(texture) = texture(name)? {
+            return Ok(Some(texture));
+        }
+
+        Ok(number(lines, name, (material_file, material_path))?
+            .map(|value| ColorKind::Solid(Color::new([value.clamp(0., 1.); 3]))))
+    };
+
+    let mut principled = Principled::default();
+    let mut found = false;
+    for (name, field) in [
+        ("Pm", &mut principled.metallic),
+        ("Pr", &mut principled.roughness),
+        ("Ps", &mut principled.sheen),
+        ("Pc", &mut principled.clearcoat),
+        ("Pcr", &mut principled.clearcoat_roughness),
+    ] {
+        if let Some(value) = parameter(name)? {
+            *field = value;
+            found = true;
+        }
+    }
+    if !found {
+        return Ok(None);
+    }
+
+    if let Some(texture) = texture("Ni")? {
+        principled.refractive_index = PrincipledRefractiveIndex::Texture(texture);
+    } else if let Some(refractive_index) =
+        number(lines, "Ni", (material_file, material_path))?.filter(|&index| index > 0.)
+    {
+        principled.refractive_index =
+            PrincipledRefractiveIndex::Spectral(RefractiveIndex::Constant(refractive_index));
+    }
+
+    Ok(Some(principled))
+}
+
+/// The number after `name` on the first line starting with it
+fn number(
+    lines: &Lines<'_>,
+    name: &str,
+    (material_file, material_path): (&str, &str),
+) -> Result<Option<f32>, ObjError> {
+    lines
+        .clone()
+        .find_map(|line| Some((line, arguments(line, name)?)))
+        .map(|(line, value)| {
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| ObjError {
+                    path: material_path.to_owned(),
+                    line: line_number(material_file, line),
+                    kind: ObjErrorKind::Parse(ParseError::new("a number", value)),
+                })
+        })
+        .transpose()
+}
+
+/// The arguments after `keyword` at the start of `line`, empty when it stands alone
+fn arguments<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let arguments = line.strip_prefix(keyword)?;
+    (arguments.is_empty() || arguments.starts_with(char::is_whitespace)).then(|| arguments.trim())
+}
+
+/// The path of the `texture` on `line` of the mtl file, relative to the directory of the obj file
+fn texture_path(
+    line: &str,
+    texture: &str,
+    parent_path: &Path,
+    (material_file, material_path): (&str, &str),
+) -> Result<String, ObjError> {
+    if texture.is_empty() {
+        return Err(ObjError {
+            path: material_path.to_owned(),
+            line: line_number(material_file, line),
+            kind: ObjErrorKind::Parse(ParseError::new("a texture file", line)),
+        });
+    }
+
+    Ok(parent_path.join(texture).display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    /// Writes `files` to `directory` and parses the first as the obj file
+    fn parse_files(directory: &TempDir, files: &[(&str, &str)]) -> Result<Vec<Triangle>, ObjError> {
+        for &(name, contents) in files {
+            directory.write(name, contents);
+        }
+
+        parse(
+            &directory.join(files[0].0),
+            &mut Interner::default(),
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut Vec::new(),
+        )
+    }
+
+    fn parse_material(section: &str) -> Material {
+        material(&section.lines(), (section, "test.mtl"), Path::new("")).unwrap()
+    }
+
+    fn glass(roughness: f32) -> Material {
+        Material::new(
+            MaterialKind::Glass {
+                refractive_index: RefractiveIndex::Constant(1.3),
+                distribution: Ggx::new([roughness; 2]),
+                absorption: None,
+                priority: 0,
+            },
+            ColorKind::Solid(Color::new([1.; 3])),
+        )
+    }
+
+    fn parse_error_line(result: &Result<Vec<Triangle>, ObjError>) -> Option<usize> {
+        match *result {
+            Err(ObjError {
+                line,
+                kind: ObjErrorKind::Parse(_),
+                ..
+            }) => line,
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn triangle() {
+        let triangles = parse_files(
+            &TempDir::new("obj-triangle"),
+            &[("triangle.obj", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n")],
+        );
+        assert_eq!(triangles.map(|triangles| triangles.len()).ok(), Some(1));
+    }
+
+    #[test]
+    fn bare_keywords() {
+        for (name, contents) in [
+            ("v", "v 0 0 0\nv\n"),
+            ("vt", "# texture coordinates\nvt\n"),
+            ("vn", "vn 0 0 1\nvn\n"),
+            ("mtllib", "\nmtllib\n"),
+        ] {
+            assert_eq!(
+                parse_error_line(&parse_files(
+                    &TempDir::new(&format!("obj-bare-{name}")),
+                    &[("bare.obj", contents)]
+                )),
+                Some(2),
+                "{name}"
             );
+        }
+    }
 
-            let index = materials.intern(material);
+    #[test]
+    fn bare_material_keywords() {
+        for name in ["Kd", "map_Kd", "map_Pm"] {
+            let material = format!("newmtl bare\n{name}\n");
+            let error = parse_files(
+                &TempDir::new(&format!("obj-bare-material-{name}")),
+                &[("bare.obj", "mtllib bare.mtl\n"), ("bare.mtl", &material)],
+            );
+            assert_eq!(parse_error_line(&error), Some(2), "{name}");
+        }
+    }
 
-            name_index.insert(name, index);
+    #[test]
+    fn glass_needs_refraction() {
+        // the cutout of a texture
+        assert_eq!(
+            parse_material("Kd 1 0 0\nd 0.5\n"),
+            Material::new(
+                MaterialKind::Lambertian,
+                ColorKind::Solid(Color::new([1., 0., 0.]))
+            )
+        );
+        for section in ["d 0.5\nNi 1.3\n", "Tr 0.5\nNi 1.3\n", "illum 7\nNi 1.3\n"] {
+            assert_eq!(parse_material(section), glass(0.), "{section}");
         }
     }
 
-    name_index
+    #[test]
+    fn roughness_of_glass_before_principled() {
+        assert_eq!(
+            parse_material("illum 7\nNi 1.3\nPr 0.3\nPm 1\n"),
+            glass(0.3)
+        );
+
+        let constant = |value| ColorKind::Solid(Color::new([value; 3]));
+        assert_eq!(
+            parse_material("Ni 1.3\nPr 0.3\nPm 1\n"),
+            Material::new(
+                MaterialKind::Principled(Box::new(Principled {
+                    metallic: constant(1.),
+                    roughness: constant(0.3),
+                    refractive_index: PrincipledRefractiveIndex::Spectral(
+                        RefractiveIndex::Constant(1.3)
+                    ),
+                    ..Principled::default()
+                })),
+                constant(0.5)
+            )
+        );
+    }
+
+    #[test]
+    fn material_next_to_obj() {
+        let directory = TempDir::new("obj-missing");
+        let error = parse_files(&directory, &[("missing.obj", "mtllib missing.mtl\n")]);
+        let Err(ObjError {
+            path,
+            kind: ObjErrorKind::Io(_),
+            ..
+        }) = error
+        else {
+            panic!("expected an io error");
+        };
+
+        assert_eq!(path, directory.join("missing.mtl"));
+    }
 }
@@ -0,0 +1,560 @@
+use std::{array, f32::consts::PI};
+
+use crate::{
+    config::ParseError,
+    luminance,
+    material::ColorKind,
+    microfacet::{self, Ggx},
+    sampler,
+    spectrum::RefractiveIndex,
+    vec3::{Color, Lerp as _, New as _},
+};
+
+/// A material mixing a diffuse base, a metal and a glass, with sheen and a clearcoat on top (Burley 2012 & 2015).
+///
+/// Every parameter is a number from 0 to 1 or a grayscale texture, the refractive index a spectral one or a texture,
+/// and the base color is the color of the material.
+#[derive(Debug, PartialEq)]
+pub struct Principled {
+    /// Blends from a dielectric to a metal, which reflects in the base color
+    pub metallic: ColorKind,
+    pub roughness: ColorKind,
+    /// The reflectance of the dielectric at normal incidence, 0.5 for 4%
+    pub specular: ColorKind,
+    /// Tints the specular reflection of the dielectric towards the base color
+    pub specular_tint: ColorKind,
+    /// A soft reflection at grazing angles, like the one of cloth
+    pub sheen: ColorKind,
+    pub sheen_tint: ColorKind,
+    /// A second, colorless reflection on top, like varnish or car paint
+    pub clearcoat: ColorKind,
+    pub clearcoat_roughness: ColorKind,
+    /// Blends the dielectric from diffuse to transmitting like glass
+    pub transmission: ColorKind,
+    /// Of the transmitting dielectric
+    pub refractive_index: PrincipledRefractiveIndex,
+}
+impl Default for Principled {
+    fn default() -> Self {
+        let constant = |value| ColorKind::Solid(Color::new([value; 3]));
+
+        Self {
+            metallic: constant(0.),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.),
+            sheen: constant(0.),
+            sheen_tint: constant(0.5),
+            clearcoat: constant(0.),
+            clearcoat_roughness: constant(0.03),
+            transmission: constant(0.),
+            refractive_index: PrincipledRefractiveIndex::Spectral(RefractiveIndex::Constant(1.5)),
+        }
+    }
+}
+impl Principled {
+    const PARAMETERS: [&str; 10] = [
+        "metallic",
+        "roughness",
+        "specular",
+        "specular_tint",
+        "sheen",
+        "sheen_tint",
+        "clearcoat",
+        "clearcoat_roughness",
+        "transmission",
+        "ior",
+    ];
+
+    /// If any of it transmits light, so it has an inside
+    pub fn is_transmissive(&self) -> bool {
+        self.transmission != ColorKind::Solid(Color::new([0.; 3]))
+    }
+    /// The parameters, which may be textures
+    pub fn textures(&self) -> impl Iterator<Item = &ColorKind> {
+        let refractive_index = match self.refractive_index {
+            PrincipledRefractiveIndex::Spectral(_) => None,
+            PrincipledRefractiveIndex::Texture(ref texture) => Some(texture),
+        };
+        [
+            &self.metallic,
+            &self.roughness,
+            &self.specular,
+            &self.specular_tint,
+            &self.sheen,
+            &self.sheen_tint,
+            &self.clearcoat,
+            &self.clearcoat_roughness,
+            &self.transmission,
+        ]
+        .into_iter()
+        .chain(refractive_index)
+    }
+    /// The parameters at the texture coordinates, with the base color sampled there
+    pub fn at(&self, base_color: Color<3, f32>, texture_coordinates: [f32; 2]) -> Lobes {
+        let parameter = |kind: &ColorKind| parameter(kind, texture_coordinates);
+        let white = Color::new([1.; 3]);
+
+        let metallic = parameter(&self.metallic);
+        let roughness = parameter(&self.roughness);
+
+        // the hue of the base color, without its brightness
+        let luminance = luminance(base_color);
+        let tint = if luminance > 0. {
+            base_color / luminance
+        } else {
+            white
+        };
+        let specular_color =
+            white.lerp(tint, parameter(&self.specular_tint)) * (0.08 * parameter(&self.specular));
+
+        Lobes {
+            base_color,
+            metallic,
+            roughness,
+            distribution: Ggx::new([roughness; 2]),
+            specular_color: specular_color.lerp(base_color, metallic),
+            sheen: white.lerp(tint, parameter(&self.sheen_tint)) * parameter(&self.sheen),
+            clearcoat: parameter(&self.clearcoat),
+            clearcoat_distribution: Ggx::new([parameter(&self.clearcoat_roughness); 2]),
+            transmission: parameter(&self.transmission),
+        }
+    }
+
+    /// Parses a principled material after its kind, pairs of a parameter and its value.
+    /// The values are numbers from 0 to 1 or the paths of ppm textures,
+    /// except `ior`, which is a refractive index like a glass's or the path of a ppm texture.
+    /// Parameters that are left out keep their defaults.
+    /// # Errors
+    /// When a parameter is unknown, or its value is out of range or an unreadable texture
+    pub fn parse<'a>(
+        split: &mut impl Iterator<Item = &'a str>,
+        value: &str,
+    ) -> Result<Self, ParseError> {
+        const EXPECTED: &str = "principled followed by pairs of metallic, roughness, specular, specular_tint, sheen, sheen_tint, clearcoat, clearcoat_roughness, transmission or ior and its value";
+        const VALUE: &str = "a number from 0 to 1 or the path of a ppm texture";
+
+        let mut principled = Self::default();
+        let mut split = split.peekable();
+
+        while let Some(name) = split.next_if(|name| Self::PARAMETERS.contains(name)) {
+            if name == "ior" {
+                let texture = split.next_if(|refractive_index| {
+                    refractive_index.parse::<f32>().is_err()
+                        && !RefractiveIndex::is_named(refractive_index)
+                });
+                principled.refractive_index = match texture {
+                    Some(texture) => PrincipledRefractiveIndex::Texture(
+                        ColorKind::texture_from_ppm_p6(texture)
+                            .map_err(|_| ParseError::new(EXPECTED, texture))?,
+                    ),
+                    None => PrincipledRefractiveIndex::Spectral(RefractiveIndex::parse(
+                        &mut split, value, EXPECTED,
+                    )?),
+                };
+                continue;
+            }
+
+            let parameter = split
+                .next()
+                .ok_or_else(|| ParseError::new(EXPECTED, value))?;
+            let parameter = match parameter.parse::<f32>() {
+                Ok(number) if (0.0..=1.).contains(&number) => {
+                    ColorKind::Solid(Color::new([number; 3]))
+                }
+                Ok(_) => return Err(ParseError::new(VALUE, parameter)),
+                Err(_) => ColorKind::texture_from_ppm_p6(parameter)
+                    .map_err(|_| ParseError::new(VALUE, parameter))?,
+            };
+
+            *match name {
+                "metallic" => &mut principled.metallic,
+                "roughness" => &mut principled.roughness,
+                "specular" => &mut principled.specular,
+                "specular_tint" => &mut principled.specular_tint,
+                "sheen" => &mut principled.sheen,
+                "sheen_tint" => &mut principled.sheen_tint,
+                "clearcoat" => &mut principled.clearcoat,
+                "clearcoat_roughness" => &mut principled.clearcoat_roughness,
+                _ => &mut principled.transmission,
+            } = parameter;
+        }
+
+        // an unknown parameter
+        if let Some(parameter) = split.peek() {
+            return Err(ParseError::new(EXPECTED, parameter));
+        }
+
+        Ok(principled)
+    }
+}
+
+/// The refractive index of a principled material
+#[derive(Debug, PartialEq)]
+pub enum PrincipledRefractiveIndex {
+    /// Which disperses light if it varies with the wavelength
+    Spectral(RefractiveIndex),
+    /// From 1 to 3 along the grayscale value of the texture, the same for every wavelength
+    Texture(ColorKind),
+}
+impl PrincipledRefractiveIndex {
+    /// The refractive index at the texture coordinates, and at `wavelength` in nanometers or at the d line
+    pub fn at(&self, wavelength: Option<f32>, texture_coordinates: [f32; 2]) -> f32 {
+        match *self {
+            Self::Spectral(refractive_index) => refractive_index.at(wavelength),
+            Self::Texture(ref texture) => 1. + 2. * parameter(texture, texture_coordinates),
+        }
+    }
+    /// The refractive index of the inside, as the surfaces nested in it see it.
+    /// A texture only covers the surface, so its inside has the default of 1.5.
+    pub const fn inside(&self) -> RefractiveIndex {
+        match *self {
+            Self::Spectral(refractive_index) => refractive_index,
+            Self::Texture(_) => RefractiveIndex::Constant(1.5),
+        }
+    }
+}
+
+/// The lobes of a principled material at one point, scattering in the local frame facing `wo`.
+/// `eta` is the refractive index of the side the transmission goes into relative to the side it comes from.
+pub struct Lobes {
+    base_color: Color<3, f32>,
+    metallic: f32,
+    roughness: f32,
+    distribution: Ggx,
+    /// The reflectance of the specular reflection at normal incidence
+    specular_color: Color<3, f32>,
+    sheen: Color<3, f32>,
+    clearcoat: f32,
+    clearcoat_distribution: Ggx,
+    transmission: f32,
+}
+impl Lobes {
+    /// The bsdf times the cosine, and the pdf of `sample` returning `wi`
+    pub fn evaluate(&self, eta: f32, wo: [f32; 3], wi: [f32; 3]) -> (Color<3, f32>, f32) {
+        let white = Color::new([1.; 3]);
+        let mut value = Color::new([0.; 3]);
+        let mut pdf = 0.;
+        if wo[2] <= 0. || wi[2] == 0. {
+            return (value, pdf);
+        }
+
+        let [
+            diffuse_probability,
+            specular_probability,
+            glass_probability,
+            clearcoat_probability,
+        ] = self.probabilities(wo[2]);
+        let dielectric = 1. - self.metallic;
+        // the light the clearcoat lets through to the layers below
+        let base = 1. - self.clearcoat_reflectance(wo[2]);
+
+        // the glass reflects and transmits by itself, tinting the transmission
+        if glass_probability > 0. {
+            let (glass, glass_pdf) =
+                microfacet::evaluate_rough_dielectric(self.distribution, eta, wo, wi);
+            let tint = if wi[2] < 0. { self.base_color } else { white };
+
+            value = value + tint * (base * dielectric * self.transmission * glass);
+            pdf += glass_probability * glass_pdf;
+        }
+        if wi[2] < 0. {
+            return (value, pdf);
+        }
+
+        let h = microfacet::normalize(array::from_fn(|i| wo[i] + wi[i]));
+        let cos = microfacet::dot(wi, h);
+
+        // the metal, and the opaque part of the dielectric
+        let d = self.distribution.d(h);
+        let specular_reflectance = self.specular_color.lerp(white, schlick_weight(cos));
+        value = value
+            + specular_reflectance
+                * (base * (1. - dielectric * self.transmission) * d * self.distribution.g2(wo, wi)
+                    / (4. * wo[2]));
+        pdf += specular_probability * self.distribution.g1(wo) * d / (4. * wo[2]);
+
+        // diffuse, brightened at grazing angles on rough surfaces
+        let retroreflection = 0.5 + 2. * self.roughness * cos * cos;
+        let diffuse = (1. + (retroreflection - 1.) * schlick_weight(wi[2]))
+            * (1. + (retroreflection - 1.) * schlick_weight(wo[2]))
+            / PI;
+        // entering and leaving through the specular reflection, so their sum stays below 1
+        let transmitted = |cos| white - self.specular_color.lerp(white, schlick_weight(cos));
+        value = value
+            + (self.base_color * diffuse + self.sheen * schlick_weight(cos))
+                * transmitted(wo[2])
+                * transmitted(wi[2])
+                * (base * dielectric * (1. - self.transmission) * wi[2]);
+        pdf += diffuse_probability * wi[2] / PI;
+
+        if clearcoat_probability > 0. {
+            let distribution = self.clearcoat_distribution;
+            let d = distribution.d(h);
+
+            value = value
+                + white
+                    * (self.clearcoat_reflectance(cos) * d * distribution.g2(wo, wi)
+                        / (4. * wo[2]));
+            pdf += clearcoat_probability * distribution.g1(wo) * d / (4. * wo[2]);
+        }
+
+        (value, pdf)
+    }
+    /// Samples one of the lobes with the same dimensions whichever it is, and returns the direction, the bsdf times the cosine divided by the pdf of all lobes, and that pdf
+    pub fn sample(&self, eta: f32, wo: [f32; 3]) -> Option<([f32; 3], Color<3, f32>, f32)> {
+        // always drawn, so the following dimensions stay aligned
+        let mut choice = sampler::get_1d();
+        let sample = sampler::get_2d();
+        if wo[2] <= 0. {
+            return None;
+        }
+
+        let probabilities = self.probabilities(wo[2]);
+        let lobe = probabilities
+            .iter()
+            .position(|&probability| {
+                choice -= probability;
+                choice < 0.
+            })
+            .or_else(|| {
+                probabilities
+                    .iter()
+                    .rposition(|&probability| probability > 0.)
+            })?;
+
+        // what is left of the choice within the lobe, uniform again
+        let random = ((choice + probabilities[lobe]) / probabilities[lobe]).clamp(0., 1.);
+
+        let wi = match lobe {
+            // cosine distributed
+            0 => {
+                let [x, y] = sampler::unit_disk(sample);
+                [x, y, (1. - x * x - y * y).max(0.).sqrt()]
+            }
+            1 => microfacet::reflect(wo, self.distribution.sample_visible_normal(wo, sample)),
+            2 => {
+                microfacet::sample_rough_dielectric_with(
+                    self.distribution,
+                    eta,
+                    wo,
+                    sample,
+                    random,
+                )?
+                .0
+            }
+            _ => microfacet::reflect(
+                wo,
+                self.clearcoat_distribution
+                    .sample_visible_normal(wo, sample),
+            ),
+        };
+        // reflected below the surface, which the shadowing terms account for
+        if lobe != 2 && wi[2] <= 0. {
+            return None;
+        }
+
+        let (value, pdf) = self.evaluate(eta, wo, wi);
+        (pdf > 0.).then(|| (wi, value / pdf, pdf))
+    }
+
+    /// The reflectance of the clearcoat at `cos` to its normal,
+    /// the one of a refractive index of 1.5 at a quarter of the strength, like Burley's
+    fn clearcoat_reflectance(&self, cos: f32) -> f32 {
+        0.25 * self.clearcoat * (0.04 + 0.96 * schlick_weight(cos))
+    }
+    /// The probabilities of sampling the diffuse, specular, glass and clearcoat lobes, seen at `cos` to the normal
+    fn probabilities(&self, cos: f32) -> [f32; 4] {
+        let dielectric = 1. - self.metallic;
+        let weights = [
+            dielectric * (1. - self.transmission),
+            1. - dielectric * self.transmission,
+            dielectric * self.transmission,
+            self.clearcoat_reflectance(cos),
+        ];
+        let total: f32 = weights.iter().sum();
+
+        weights.map(|weight| weight / total)
+    }
+}
+
+/// The grayscale value of a parameter at the texture coordinates, from 0 to 1
+fn parameter(kind: &ColorKind, texture_coordinates: [f32; 2]) -> f32 {
+    let [r, g, b] = kind.sample(texture_coordinates).into_inner();
+    ((r + g + b) / 3.).clamp(0., 1.)
+}
+
+/// How much the Fresnel term rises towards 1 at `cos` to the normal, by Schlick's approximation
+fn schlick_weight(cos: f32) -> f32 {
+    (1. - cos).clamp(0., 1.).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sampler::SamplerKind, testing::TempDir};
+
+    fn constant(value: f32) -> ColorKind {
+        ColorKind::Solid(Color::new([value; 3]))
+    }
+
+    /// The fraction of light a white material reflects and transmits seen at `cos` to the normal, and its standard error
+    fn albedo(principled: &Principled, cos: f32) -> (f64, f64) {
+        const SAMPLES: u32 = 100_000;
+
+        let lobes = principled.at(Color::new([1.; 3]), [0.; 2]);
+        let wo = [(1. - cos * cos).sqrt(), 0., cos];
+        let [mut sum, mut sum_squared] = [0.; 2];
+        for index in 0..SAMPLES {
+            sampler::start_sample(SamplerKind::Independent, 0, u64::from(index), SAMPLES, 0);
+            if let Some((_, weight, _)) = lobes.sample(1.5, wo) {
+                let weight = f64::from(weight.into_inner()[0]);
+                sum += weight;
+                sum_squared += weight * weight;
+            }
+        }
+
+        let samples = f64::from(SAMPLES);
+        let mean = sum / samples;
+        (
+            mean,
+            ((sum_squared / samples - mean.powi(2)) / samples).sqrt(),
+        )
+    }
+
+    #[test]
+    fn white_furnace() {
+        for (name, principled) in [
+            ("default", Principled::default()),
+            (
+                "rough",
+                Principled {
+                    roughness: constant(1.),
+                    ..Principled::default()
+                },
+            ),
+            (
+                "sheen and clearcoat",
+                Principled {
+                    sheen: constant(1.),
+                    clearcoat: constant(1.),
+                    clearcoat_roughness: constant(0.3),
+                    ..Principled::default()
+                },
+            ),
+            (
+                "specular",
+                Principled {
+                    roughness: constant(0.2),
+                    specular: constant(1.),
+                    ..Principled::default()
+                },
+            ),
+            (
+                "coated metal",
+                Principled {
+                    metallic: constant(1.),
+                    roughness: constant(0.),
+                    clearcoat: constant(1.),
+                    ..Principled::default()
+                },
+            ),
+            (
+                "coated glass",
+                Principled {
+                    roughness: constant(0.3),
+                    clearcoat: constant(1.),
+                    transmission: constant(1.),
+                    ..Principled::default()
+                },
+            ),
+        ] {
+            for cos in [1., 0.7, 0.5, 0.3, 0.1] {
+                let (albedo, error) = albedo(&principled, cos);
+                assert!(
+                    albedo <= 1. + 3. * error,
+                    "{name} at {cos}: {albedo} ± {error}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_metal_reflects_everything() {
+        let principled = Principled {
+            metallic: constant(1.),
+            roughness: constant(0.),
+            ..Principled::default()
+        };
+
+        for cos in [1., 0.5, 0.1] {
+            let (albedo, _) = albedo(&principled, cos);
+            assert!((albedo - 1.).abs() < 1e-3, "{cos}: {albedo}");
+        }
+    }
+
+    #[test]
+    fn textured_refractive_index() {
+        let directory = TempDir::new("principled-ior");
+        let value = format!("ior {}", directory.write("ior.ppm", b"P6\n1 1\n255\n333"));
+
+        let principled = Principled::parse(&mut value.split_whitespace(), &value).unwrap();
+        let refractive_index = &principled.refractive_index;
+        // a grayscale value of 0.2, the same for every wavelength
+        for wavelength in [None, Some(400.)] {
+            assert!((refractive_index.at(wavelength, [0.5; 2]) - 1.4).abs() < 1e-6);
+        }
+        assert_eq!(refractive_index.inside(), RefractiveIndex::Constant(1.5));
+    }
+
+    #[test]
+    fn lobes_draw_the_same_dimensions() {
+        let lobes = Principled {
+            transmission: constant(0.5),
+            clearcoat: constant(1.),
+            ..Principled::default()
+        }
+        .at(Color::new([1.; 3]), [0.; 2]);
+        let wo = [0.6, 0., 0.8];
+
+        for index in 0..1000 {
+            sampler::start_sample(SamplerKind::Independent, 0, index, 1, 0);
+            sampler::get_1d();
+            sampler::get_2d();
+            let expected = sampler::get_1d();
+
+            sampler::start_sample(SamplerKind::Independent, 0, index, 1, 0);
+            lobes.sample(1.5, wo);
+            assert_eq!(sampler::get_1d().to_bits(), expected.to_bits(), "{index}");
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let parse = |value: &str| Principled::parse(&mut value.split_whitespace(), value);
+
+        assert_eq!(parse(""), Ok(Principled::default()));
+        assert_eq!(
+            parse("metallic 1 roughness 0.2 ior 1.33"),
+            Ok(Principled {
+                metallic: constant(1.),
+                roughness: constant(0.2),
+                refractive_index: PrincipledRefractiveIndex::Spectral(RefractiveIndex::Constant(
+                    1.33
+                )),
+                ..Principled::default()
+            })
+        );
+        for invalid in [
+            "shininess 1",
+            "metallic",
+            "metallic 1.5",
+            "roughness missing.ppm",
+            "ior",
+            "ior missing.ppm",
+            "metallic 1 0.5",
+        ] {
+            assert!(parse(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -98,6 +98,25 @@ pub fn with_rng<T>(f: impl Fn(&mut Rng) -> T) -> T {
     })
 }
 
+/// Seeds the current thread's rng from the pixel, the sample index and the render seed.
+/// This makes renders reproducible, independent of which thread renders which sample.
+pub fn seed_sample(pixel: u64, sample: u64, seed: u64) {
+    RNG.set(Rng(hash([seed, pixel, sample])));
+}
+
+/// Combines the values into a well distributed hash
+pub fn hash(values: impl IntoIterator<Item = u64>) -> u64 {
+    values.into_iter().fold(0, |hash, value| mix(hash ^ value))
+}
+
+/// The splitmix64 finalizer, decorrelates similar inputs
+const fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
 pub trait Random {
     fn random() -> Self;
 }
@@ -113,3 +132,49 @@ macro_rules! impl_random {
     };
 }
 impl_random!(f32, u32, u64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_decorrelates_similar_values() {
+        assert_eq!(hash([1, 2, 3]), hash([1, 2, 3]));
+        assert_ne!(hash([1, 2, 3]), hash([3, 2, 1]));
+        assert_ne!(hash([0]), hash([0, 0]));
+
+        // neighbouring pixels differ in about half of their bits
+        for pixel in 0..100 {
+            let differing = (hash([0, pixel, 0]) ^ hash([0, pixel + 1, 0])).count_ones();
+            assert!((16..=48).contains(&differing), "{pixel}: {differing}");
+        }
+    }
+
+    #[test]
+    fn seeded_samples_are_reproducible() {
+        let values = || {
+            seed_sample(7, 3, 42);
+            [u64::random(), u64::random()]
+        };
+        let first = values();
+        u64::random();
+        assert_eq!(values(), first);
+
+        seed_sample(7, 4, 42);
+        assert_ne!(u64::random(), first[0]);
+    }
+
+    #[test]
+    fn floats_are_in_range() {
+        let mut rng = Rng(1);
+        for _ in 0..1000 {
+            let [a, b] = rng.f32_by_two();
+            for value in [rng.f32(), a, b]
+                .into_iter()
+                .chain(rng.simd_f32().to_array())
+            {
+                assert!((0.0..1.).contains(&value), "{value}");
+            }
+        }
+    }
+}
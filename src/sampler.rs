@@ -0,0 +1,414 @@
+use std::{
+    cell::Cell,
+    f32::consts::{FRAC_PI_4, PI, TAU},
+};
+
+use crate::{
+    config::ParseError,
+    rng::{self, Random as _},
+    vec3::NormalizedVector3,
+};
+
+thread_local! {
+    static SAMPLE: Cell<Sample> = const { Cell::new(Sample {
+        kind: SamplerKind::Independent,
+        pixel_seed: 0,
+        index: 0,
+        samples_per_pixel: 1,
+        dimension: 0,
+    }) };
+}
+
+/// Generates the sample values of the current thread's sample, one dimension after another
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplerKind {
+    /// White noise
+    Independent,
+    /// Jittered strata, shuffled per dimension
+    Stratified,
+    /// The Halton sequence, randomized per pixel
+    Halton,
+    /// The Sobol sequence with Owen scrambling, padded in pairs of dimensions
+    Sobol,
+}
+impl TryFrom<&str> for SamplerKind {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "independent" => Ok(Self::Independent),
+            "stratified" => Ok(Self::Stratified),
+            "halton" => Ok(Self::Halton),
+            "sobol" => Ok(Self::Sobol),
+            other => Err(ParseError::new(
+                "one of independent, stratified, halton or sobol",
+                other,
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Sample {
+    kind: SamplerKind,
+    /// Decorrelates the pixels
+    pixel_seed: u64,
+    /// The index of the sample in its pixel
+    index: u64,
+    samples_per_pixel: u32,
+    /// The next dimension to be sampled
+    dimension: u32,
+}
+
+/// Starts the `index`th sample of `pixel` on the current thread, also seeding the rng
+pub fn start_sample(kind: SamplerKind, pixel: u64, index: u64, samples_per_pixel: u32, seed: u64) {
+    rng::seed_sample(pixel, index, seed);
+
+    SAMPLE.set(Sample {
+        kind,
+        pixel_seed: rng::hash([pixel, seed]),
+        index,
+        samples_per_pixel,
+        dimension: 0,
+    });
+}
+
+/// The next sample value, 0..1
+pub fn get_1d() -> f32 {
+    let mut sample = SAMPLE.get();
+    let dimension = sample.dimension;
+    sample.dimension += 1;
+    SAMPLE.set(sample);
+
+    match sample.kind {
+        SamplerKind::Independent => f32::random(),
+        SamplerKind::Stratified => stratum(&sample, dimension, sample.samples_per_pixel)
+            .map_or_else(f32::random, |stratum| {
+                #[expect(clippy::cast_precision_loss)]
+                let value = (stratum as f32 + f32::random()) / sample.samples_per_pixel as f32;
+                value
+            }),
+        SamplerKind::Halton => halton(&sample, dimension).unwrap_or_else(f32::random),
+        SamplerKind::Sobol => {
+            #[expect(clippy::cast_possible_truncation)]
+            let index = owen_scramble(sample.index as u32, sample.seed(dimension));
+            to_f32(owen_scramble(
+                sobol(index, 0),
+                sample.seed(dimension) ^ 0xa511_e9b3,
+            ))
+        }
+    }
+}
+
+/// The next two sample values, [0..1; 2]
+pub fn get_2d() -> [f32; 2] {
+    let mut sample = SAMPLE.get();
+    let dimension = sample.dimension;
+    sample.dimension += 2;
+    SAMPLE.set(sample);
+
+    match sample.kind {
+        SamplerKind::Independent => [f32::random(), f32::random()],
+        SamplerKind::Stratified => {
+            // a grid of x_strata * y_strata, which might not be filled completely
+            #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            #[expect(clippy::cast_precision_loss)]
+            let x_strata = ((sample.samples_per_pixel as f32).sqrt() as u32).max(1);
+            let y_strata = sample.samples_per_pixel.div_ceil(x_strata);
+
+            stratum(&sample, dimension, x_strata * y_strata).map_or_else(
+                || [f32::random(), f32::random()],
+                |stratum| {
+                    #[expect(clippy::cast_precision_loss)]
+                    #[expect(clippy::integer_division)]
+                    [
+                        ((stratum % x_strata) as f32 + f32::random()) / x_strata as f32,
+                        ((stratum / x_strata) as f32 + f32::random()) / y_strata as f32,
+                    ]
+                },
+            )
+        }
+        SamplerKind::Halton => match [halton(&sample, dimension), halton(&sample, dimension + 1)] {
+            [Some(x), Some(y)] => [x, y],
+            _ => [f32::random(), f32::random()],
+        },
+        SamplerKind::Sobol => {
+            // shuffle the points per pair of dimensions, so they stay uncorrelated
+            let seed = sample.seed(dimension);
+            #[expect(clippy::cast_possible_truncation)]
+            let index = owen_scramble(sample.index as u32, seed);
+
+            [
+                to_f32(owen_scramble(sobol(index, 0), seed ^ 0xa511_e9b3)),
+                to_f32(owen_scramble(sobol(index, 1), seed ^ 0x63d8_3595)),
+            ]
+        }
+    }
+}
+
+impl Sample {
+    #[expect(clippy::cast_possible_truncation)]
+    fn seed(&self, dimension: u32) -> u32 {
+        rng::hash([self.pixel_seed, dimension.into()]) as u32
+    }
+}
+
+/// The shuffled stratum of the sample, out of `strata`. None if there are more samples than strata.
+fn stratum(sample: &Sample, dimension: u32, strata: u32) -> Option<u32> {
+    (sample.index < strata.into()).then(|| {
+        #[expect(clippy::cast_possible_truncation)]
+        permute(sample.index as u32, strata, sample.seed(dimension))
+    })
+}
+
+/// The first bases of the Halton sequence
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// The Halton sequence with a per-pixel random rotation. None if the dimension exceeds the amount of bases.
+#[expect(clippy::cast_possible_truncation)]
+#[expect(clippy::cast_precision_loss)]
+fn halton(sample: &Sample, dimension: u32) -> Option<f32> {
+    let &base = PRIMES.get(dimension as usize)?;
+
+    // radical inverse
+    let mut index = sample.index;
+    let mut reversed = 0;
+    let mut inverse_base_power = 1.;
+    while index > 0 {
+        reversed = reversed * base + index % base;
+        inverse_base_power /= base as f64;
+        index /= base;
+    }
+    let value = reversed as f64 * inverse_base_power;
+
+    // Cranley-Patterson rotation
+    let offset = f64::from(sample.seed(dimension)) / f64::from(u32::MAX);
+
+    Some(((value + offset).fract() as f32).min(ONE_MINUS_EPSILON))
+}
+
+/// The first two dimensions of the Sobol sequence
+const fn sobol(index: u32, dimension: u8) -> u32 {
+    let mut result = 0;
+    let mut direction = 1 << 31;
+
+    let mut bit = 0;
+    while bit < 32 {
+        if (index >> bit) & 1 == 1 {
+            result ^= direction;
+        }
+        // the direction numbers of the primitive polynomials 1 and x + 1
+        direction = if dimension == 0 {
+            direction >> 1
+        } else {
+            direction ^ (direction >> 1)
+        };
+        bit += 1;
+    }
+
+    result
+}
+
+/// Nested uniform scrambling, using the Laine-Karras hash (Burley 2020)
+const fn owen_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+
+    x.reverse_bits()
+}
+
+/// The element at `index` of a random permutation of 0..len (Kensler 2013)
+const fn permute(index: u32, len: u32, seed: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // cycle walking, until the index lands in 0..len
+    let mut index = index;
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & w) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & w) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & w) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & w) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & w) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= w;
+        index ^= index >> 5;
+
+        if index < len {
+            return index.wrapping_add(seed) % len;
+        }
+    }
+}
+
+/// The largest f32 below 1
+const ONE_MINUS_EPSILON: f32 = 1. - f32::EPSILON / 2.;
+
+/// Maps a u32 to 0..1, keeping the 24 most significant bits
+#[expect(clippy::cast_precision_loss)]
+const fn to_f32(x: u32) -> f32 {
+    (x >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Maps a sample to a uniformly distributed point in the unit disk, preserving stratification (Shirley-Chiu)
+pub fn unit_disk([u, v]: [f32; 2]) -> [f32; 2] {
+    let [x, y] = [2. * u - 1., 2. * v - 1.];
+
+    if x == 0. && y == 0. {
+        return [0.; 2];
+    }
+
+    let (radius, angle) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, PI / 2. - FRAC_PI_4 * (x / y))
+    };
+
+    [radius * angle.cos(), radius * angle.sin()]
+}
+
+/// Maps a sample to a uniformly distributed direction
+pub fn unit_sphere([u, v]: [f32; 2]) -> NormalizedVector3 {
+    let z = 1. - 2. * u;
+    let radius = (1. - z * z).max(0.).sqrt();
+    let angle = TAU * v;
+
+    NormalizedVector3::new_unchecked([radius * angle.cos(), radius * angle.sin(), z])
+}
+
+/// Two directions perpendicular to `normal` and each other (Duff et al. 2017)
+pub fn orthonormal_basis(normal: NormalizedVector3) -> [NormalizedVector3; 2] {
+    let [x, y, z] = normal.into_inner();
+
+    let sign = 1_f32.copysign(z);
+    let factor = -1. / (sign + z);
+    let xy = x * y * factor;
+
+    [
+        NormalizedVector3::new_unchecked([1. + sign * x * x * factor, sign * xy, -sign * x]),
+        NormalizedVector3::new_unchecked([xy, sign + y * y * factor, -y]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The next two sample values of every sample of a pixel, after `skipped` dimensions
+    fn points(kind: SamplerKind, samples_per_pixel: u32, skipped: u32) -> Vec<[f32; 2]> {
+        (0..samples_per_pixel)
+            .map(|index| {
+                start_sample(kind, 5, index.into(), samples_per_pixel, 9);
+                for _ in 0..skipped {
+                    get_1d();
+                }
+                get_2d()
+            })
+            .collect()
+    }
+
+    /// If every cell of a `width` by `height` grid holds exactly one of the points
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    #[expect(clippy::cast_precision_loss)]
+    fn one_per_cell(points: &[[f32; 2]], [width, height]: [u32; 2]) -> bool {
+        let mut counts = vec![0; (width * height) as usize];
+        for &[x, y] in points {
+            assert!((0.0..1.).contains(&x) && (0.0..1.).contains(&y), "{x}, {y}");
+            counts[(x * width as f32) as usize + (y * height as f32) as usize * width as usize] +=
+                1;
+        }
+
+        counts.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(SamplerKind::try_from("sobol"), Ok(SamplerKind::Sobol));
+        assert_eq!(
+            SamplerKind::try_from("random"),
+            Err(ParseError::new(
+                "one of independent, stratified, halton or sobol",
+                "random"
+            ))
+        );
+    }
+
+    #[test]
+    fn stratified() {
+        for skipped in [0, 3] {
+            assert!(one_per_cell(
+                &points(SamplerKind::Stratified, 16, skipped),
+                [4, 4]
+            ));
+        }
+        // a grid that isn't square
+        assert!(one_per_cell(&points(SamplerKind::Stratified, 8, 0), [2, 4]));
+
+        let values: Vec<_> = (0..8)
+            .map(|index| {
+                start_sample(SamplerKind::Stratified, 5, index, 8, 9);
+                [get_1d(), 0.]
+            })
+            .collect();
+        assert!(one_per_cell(&values, [8, 1]));
+    }
+
+    #[test]
+    fn halton() {
+        // the first powers of every base stratify its dimension, even when rotated
+        let points_2 = points(SamplerKind::Halton, 16, 0);
+        assert!(one_per_cell(&points_2, [16, 1]));
+        let points_3 = points(SamplerKind::Halton, 9, 0);
+        assert!(one_per_cell(&points_3, [1, 9]));
+    }
+
+    #[test]
+    fn sobol() {
+        // every elementary interval of 16 points holds one of them
+        for skipped in [0, 2] {
+            let points = points(SamplerKind::Sobol, 16, skipped);
+            for cells in [[16, 1], [8, 2], [4, 4], [2, 8], [1, 16]] {
+                assert!(one_per_cell(&points, cells), "{cells:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn reproducible() {
+        for kind in [
+            SamplerKind::Independent,
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let bits = || {
+                points(kind, 16, 1)
+                    .into_iter()
+                    .map(|point| point.map(f32::to_bits))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(bits(), bits(), "{kind:?}");
+        }
+    }
+}
@@ -11,6 +11,7 @@ use crate::{
     Ray, SCENE,
     indices::{HasIndexer, Indexer},
     material::Material,
+    sampler,
     vec3::{New as _, NormalizedVector3, Point, Point3, Vector3},
 };
 
@@ -53,6 +54,45 @@ impl Sphere {
             material_index,
         }
     }
+    /// Samples a direction from `origin` uniformly within the cone the sphere covers.
+    /// Returns the direction and its pdf per solid angle, None if `origin` is inside of the sphere.
+    pub fn sample_direction(
+        &self,
+        origin: Point3,
+        [u, v]: [f32; 2],
+    ) -> Option<(NormalizedVector3, f32)> {
+        let to_center = origin.vector_to(self.center);
+        let sin_max_squared = self.radius * self.radius / to_center.length_squared();
+        if sin_max_squared >= 1. {
+            return None;
+        }
+
+        let one_minus_cos_max = one_minus_cos_max(sin_max_squared);
+        let cos = 1. - u * one_minus_cos_max;
+        let sin = (1. - cos * cos).max(0.).sqrt();
+        let angle = TAU * v;
+
+        let forward = to_center.normalize::<f32>();
+        let [a, b] = sampler::orthonormal_basis(forward);
+        let direction =
+            (forward * cos + a * (sin * angle.cos()) + b * (sin * angle.sin())).normalize::<f32>();
+
+        Some((direction, 1. / (TAU * one_minus_cos_max)))
+    }
+    /// The pdf per solid angle of `sample_direction` for any direction towards the sphere, 0 if `origin` is inside of it
+    pub fn direction_pdf(&self, origin: Point3) -> f32 {
+        let sin_max_squared =
+            self.radius * self.radius / origin.vector_to(self.center).length_squared();
+        if sin_max_squared >= 1. {
+            return 0.;
+        }
+
+        1. / (TAU * one_minus_cos_max(sin_max_squared))
+    }
+}
+/// 1 - cos of the cone around a sphere, without cancellation for far away spheres
+fn one_minus_cos_max(sin_max_squared: f32) -> f32 {
+    sin_max_squared / (1. + (1. - sin_max_squared).sqrt())
 }
 impl Intersects for Sphere {
     // See `ray_sphere_intersection_derivation.latex` for the formula used here
@@ -207,6 +247,44 @@ impl Triangle {
             material_index,
         }
     }
+    /// Samples a point uniformly on the triangle.
+    /// Returns the direction from `origin` towards it and its pdf per solid angle, None if the triangle is seen edge-on.
+    pub fn sample_direction(
+        &self,
+        origin: Point3,
+        [u, v]: [f32; 2],
+    ) -> Option<(NormalizedVector3, f32)> {
+        let root = u.sqrt();
+        let point = self.a + self.e1 * (root * (1. - v)) + self.e2 * (root * v);
+
+        let to_point = origin.vector_to(point);
+        let direction = to_point.normalize::<f32>();
+        let pdf = self.direction_pdf(direction, to_point.length());
+
+        (pdf > 0.).then_some((direction, pdf))
+    }
+    /// The pdf per solid angle of `sample_direction` for `direction`, which hits the triangle after `distance`.
+    /// 0 if the triangle is seen edge-on.
+    pub fn direction_pdf(&self, direction: NormalizedVector3, distance: f32) -> f32 {
+        let cross = self.e1.cross(self.e2);
+        let area = cross.length::<f32>() / 2.;
+        let cos = cross.normalize::<f32>().dot(direction).abs();
+
+        // convert the pdf from per area to per solid angle
+        if cos > 1e-6 {
+            distance.powi(2) / (cos * area)
+        } else {
+            0.
+        }
+    }
+    /// The barycentric coordinates of a point on the triangle, without the precomputed values of textured triangles
+    pub fn barycentrics(&self, point: &Point3) -> [f32; 3] {
+        let d00 = self.e1.dot(self.e1);
+        let d01 = self.e1.dot(self.e2);
+        let d11 = self.e2.dot(self.e2);
+
+        self.barycentric_coordinates(point, [d00, d01, d11, d00 * d11 - d01.powi(2)])
+    }
     fn barycentric_coordinates(
         &self,
         point: &Point3,
@@ -368,3 +446,63 @@ pub enum NormalsTextureCoordinates {
     },
     None,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples on a grid over the unit square
+    fn grid(size: u16) -> impl Iterator<Item = [f32; 2]> {
+        let size_f32 = f32::from(size);
+        (0..size).flat_map(move |i| {
+            (0..size).map(move |j| {
+                [
+                    (f32::from(i) + 0.5) / size_f32,
+                    (f32::from(j) + 0.5) / size_f32,
+                ]
+            })
+        })
+    }
+
+    #[test]
+    fn sphere_directions() {
+        let sphere = Sphere::new(Point3::new([0., 0., 4.]), 1., Indexer::new(0));
+        let origin = Point3::new([0.; 3]);
+        // the cone has a cosine of √15 / 4
+        let expected_pdf = 1. / (TAU * (1. - 15_f32.sqrt() / 4.));
+
+        for sample in grid(16) {
+            let (direction, pdf) = sphere.sample_direction(origin, sample).unwrap();
+            assert!((pdf / expected_pdf - 1.).abs() < 1e-3);
+            assert!((sphere.direction_pdf(origin) / expected_pdf - 1.).abs() < 1e-3);
+            assert!(sphere.intersects(&Ray::new(origin, direction)).is_some());
+        }
+
+        // inside of the sphere
+        let inside = Point3::new([0., 0., 4.5]);
+        assert_eq!(sphere.sample_direction(inside, [0.5; 2]), None);
+        assert!(sphere.direction_pdf(inside) <= 0.);
+    }
+
+    #[test]
+    fn triangle_directions() {
+        let triangle = Triangle::new(
+            Point3::new([-0.5, -0.5, 10.]),
+            Point3::new([0.5, -0.5, 10.]),
+            Point3::new([-0.5, 0.5, 10.]),
+            NormalsTextureCoordinates::None,
+            Indexer::new(0),
+        );
+        let origin = Point3::new([0.; 3]);
+
+        // the inverse pdf averages to the solid angle, about the area over the squared distance
+        let mut solid_angle = 0.;
+        for sample in grid(32) {
+            let (direction, pdf) = triangle.sample_direction(origin, sample).unwrap();
+            let distance = triangle.intersects(&Ray::new(origin, direction)).unwrap();
+            assert!((triangle.direction_pdf(direction, distance) / pdf - 1.).abs() < 1e-3);
+            solid_angle += 1. / pdf / 1024.;
+        }
+        assert!((solid_angle / 0.005 - 1.).abs() < 0.01, "{solid_angle}");
+    }
+}
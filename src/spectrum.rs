@@ -0,0 +1,395 @@
+use std::{array, sync::LazyLock};
+
+use crate::{
+    config::ParseError,
+    vec3::{Color, New as _},
+};
+
+/// The visible wavelengths in nanometers, which spectral paths sample uniformly
+const MIN_WAVELENGTH: f32 = 380.;
+const MAX_WAVELENGTH: f32 = 780.;
+/// The number of wavelengths a spectral path carries
+pub const WAVELENGTHS: usize = 4;
+
+/// How the path integrator carries light, as rgb or as a spectrum sampled at `N` wavelengths
+pub trait ColorModel<const N: usize> {
+    /// The wavelength refractive indices are taken at, None for rgb
+    fn wavelength(&self) -> Option<f32>;
+    /// The rgb `color` as carried by the path.
+    /// It is linear, so colors multiplied by scalars, like bsdf weights, can still be converted.
+    fn upsample(&self, color: Color<3, f32>) -> Color<N, f32>;
+    /// Only follows the wavelength refractive indices are taken at from now on,
+    /// as a dispersive surface sends every wavelength into a different direction
+    fn terminate_secondary(&mut self);
+    /// The rgb color of the light carried by the path
+    fn to_rgb(&self, color: Color<N, f32>) -> Color<3, f32>;
+}
+
+/// Carries light as rgb, the default
+#[derive(Clone, Copy, Debug)]
+pub struct Rgb;
+impl ColorModel<3> for Rgb {
+    fn wavelength(&self) -> Option<f32> {
+        None
+    }
+    fn upsample(&self, color: Color<3, f32>) -> Color<3, f32> {
+        color
+    }
+    fn terminate_secondary(&mut self) {}
+    fn to_rgb(&self, color: Color<3, f32>) -> Color<3, f32> {
+        color
+    }
+}
+
+/// The wavelengths a spectral path carries, a uniformly sampled hero wavelength
+/// and the others evenly spaced from it over the visible range (Wilkie et al. 2014)
+#[derive(Clone, Copy, Debug)]
+pub struct Wavelengths {
+    /// In nanometers, starting with the hero wavelength
+    nanometers: [f32; WAVELENGTHS],
+    /// The pdf of every wavelength, 0 once it is terminated
+    pdf: [f32; WAVELENGTHS],
+    /// The spectral value of the rgb channels at every wavelength
+    upsampling: [[f32; 3]; WAVELENGTHS],
+}
+impl Wavelengths {
+    pub fn sample(random: f32) -> Self {
+        let range = MAX_WAVELENGTH - MIN_WAVELENGTH;
+        let calibration = &*CALIBRATION;
+
+        #[expect(clippy::cast_precision_loss)]
+        let wavelengths = array::from_fn(|i| {
+            MIN_WAVELENGTH + (random + i as f32 / WAVELENGTHS as f32).fract() * range
+        });
+
+        Self {
+            nanometers: wavelengths,
+            pdf: [1. / range; WAVELENGTHS],
+            upsampling: wavelengths.map(|wavelength| {
+                let basis = basis(wavelength);
+                array::from_fn(|channel| {
+                    (0..3)
+                        .map(|j| basis[j] * calibration.upsampling[j][channel])
+                        .sum()
+                })
+            }),
+        }
+    }
+}
+impl ColorModel<WAVELENGTHS> for Wavelengths {
+    fn wavelength(&self) -> Option<f32> {
+        Some(self.nanometers[0])
+    }
+    fn upsample(&self, color: Color<3, f32>) -> Color<WAVELENGTHS, f32> {
+        let color = color.inner();
+
+        Color::new(self.upsampling.map(|upsampling| {
+            upsampling[0] * color[0] + upsampling[1] * color[1] + upsampling[2] * color[2]
+        }))
+    }
+    #[expect(clippy::cast_precision_loss)]
+    fn terminate_secondary(&mut self) {
+        if self.pdf[1] == 0. {
+            return;
+        }
+
+        // the hero wavelength now stands in for all of them
+        self.pdf[0] /= WAVELENGTHS as f32;
+        for pdf in &mut self.pdf[1..] {
+            *pdf = 0.;
+        }
+    }
+    /// Monte Carlo integrates the spectrum against the rgb matching functions
+    #[expect(clippy::cast_precision_loss)]
+    fn to_rgb(&self, color: Color<WAVELENGTHS, f32>) -> Color<3, f32> {
+        let calibration = &*CALIBRATION;
+        let mut rgb = [0.; 3];
+
+        for ((&wavelength, &pdf), &radiance) in self
+            .nanometers
+            .iter()
+            .zip(&self.pdf)
+            .zip(color.inner())
+            .filter(|&((_, &pdf), _)| pdf > 0.)
+        {
+            let matching = rgb_matching(wavelength);
+            for channel in 0..3 {
+                rgb[channel] += matching[channel] / calibration.white[channel] * radiance
+                    / (pdf * WAVELENGTHS as f32);
+            }
+        }
+
+        Color::new(rgb)
+    }
+}
+
+/// The rgb matching functions and the upsampling of rgb to spectra, computed once
+struct Calibration {
+    /// The integral of every rgb matching function, which the equal energy spectrum maps to.
+    /// Dividing by it balances that spectrum to white.
+    white: [f32; 3],
+    /// How much of every basis spectrum makes up a channel, so upsampled colors convert back to themselves
+    upsampling: [[f32; 3]; 3],
+}
+static CALIBRATION: LazyLock<Calibration> = LazyLock::new(|| {
+    // integrated in steps of 1 nanometer
+    #[expect(clippy::cast_precision_loss)]
+    let wavelengths = || {
+        (0..400).map(|step| {
+            MIN_WAVELENGTH + (step as f32 + 0.5) * (MAX_WAVELENGTH - MIN_WAVELENGTH) / 400.
+        })
+    };
+    let step = (MAX_WAVELENGTH - MIN_WAVELENGTH) / 400.;
+
+    let mut white = [0.; 3];
+    for wavelength in wavelengths() {
+        let matching = rgb_matching(wavelength);
+        for channel in 0..3 {
+            white[channel] += matching[channel] * step;
+        }
+    }
+
+    // the rgb color of every basis spectrum
+    let mut colors = [[0.; 3]; 3];
+    for wavelength in wavelengths() {
+        let matching = rgb_matching(wavelength);
+        let basis = basis(wavelength);
+        for channel in 0..3 {
+            for j in 0..3 {
+                colors[channel][j] += matching[channel] / white[channel] * basis[j] * step;
+            }
+        }
+    }
+
+    Calibration {
+        white,
+        upsampling: invert(colors),
+    }
+});
+
+/// Three smooth spectra for the red, green and blue ends of the visible range, which add up to 1 everywhere.
+/// The sRGB primaries lie just inside the colors they span, so upsampled reflectances stay between 0 and 1.
+fn basis(wavelength: f32) -> [f32; 3] {
+    let step = |edge: f32| 1. / (1. + ((edge - wavelength) / 3.).exp());
+    let [blue_green, green_red] = [494., 588.].map(step);
+
+    [green_red, blue_green - green_red, 1. - blue_green]
+}
+
+/// The CIE 1931 color matching functions converted to linear sRGB
+fn rgb_matching(wavelength: f32) -> [f32; 3] {
+    const XYZ_TO_RGB: [[f32; 3]; 3] = [
+        [3.240_454_2, -1.537_138_5, -0.498_531_4],
+        [-0.969_266, 1.876_010_8, 0.041_556],
+        [0.055_643_4, -0.204_025_9, 1.057_225_2],
+    ];
+
+    let xyz = xyz_matching(wavelength);
+    XYZ_TO_RGB.map(|row| row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2])
+}
+
+/// The CIE 1931 color matching functions, fitted with piecewise gaussians (Wyman et al. 2013)
+fn xyz_matching(wavelength: f32) -> [f32; 3] {
+    let gaussian = |mean: f32, below: f32, above: f32| {
+        let width = if wavelength < mean { below } else { above };
+        (-0.5 * ((wavelength - mean) / width).powi(2)).exp()
+    };
+
+    [
+        1.056 * gaussian(599.8, 37.9, 31.0) + 0.362 * gaussian(442.0, 16.0, 26.7)
+            - 0.065 * gaussian(501.1, 20.4, 26.2),
+        0.821 * gaussian(568.8, 46.9, 40.5) + 0.286 * gaussian(530.9, 16.3, 31.1),
+        1.217 * gaussian(437.0, 11.8, 36.0) + 0.681 * gaussian(459.0, 26.0, 13.8),
+    ]
+}
+
+fn invert(m: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let cofactor = |row: usize, column: usize| {
+        let [r0, r1] = [(row + 1) % 3, (row + 2) % 3];
+        let [c0, c1] = [(column + 1) % 3, (column + 2) % 3];
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let determinant = (0..3)
+        .map(|column| m[0][column] * cofactor(0, column))
+        .sum::<f32>();
+
+    array::from_fn(|row| array::from_fn(|column| cofactor(column, row) / determinant))
+}
+
+/// The refractive index of a dielectric, which can vary with the wavelength.
+/// Wavelengths are in micrometers in the formulas, and rgb paths take it at the sodium d line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RefractiveIndex {
+    Constant(f32),
+    /// n = a + b / λ²
+    Cauchy {
+        a: f32,
+        b: f32,
+    },
+    /// n² = 1 + Σ b λ² / (λ² - c)
+    Sellmeier {
+        b: [f32; 3],
+        c: [f32; 3],
+    },
+}
+impl RefractiveIndex {
+    const D_LINE: f32 = 587.56;
+    const PRESETS: [(&str, Self); 3] = [
+        (
+            "bk7",
+            Self::Sellmeier {
+                b: [1.039_612, 0.231_792_34, 1.010_469_5],
+                c: [0.006_000_699, 0.020_017_914, 103.560_65],
+            },
+        ),
+        (
+            "silica",
+            Self::Sellmeier {
+                b: [0.696_166_3, 0.407_942_6, 0.897_479_4],
+                c: [0.004_679_149, 0.013_512_063, 97.934_0],
+            },
+        ),
+        (
+            "diamond",
+            Self::Sellmeier {
+                b: [0.330_6, 4.335_6, 0.],
+                c: [0.030_625, 0.011_236, 0.],
+            },
+        ),
+    ];
+
+    /// The refractive index at `wavelength` in nanometers, or at the d line
+    pub fn at(self, wavelength: Option<f32>) -> f32 {
+        let wavelength = wavelength.unwrap_or(Self::D_LINE) / 1000.;
+        let squared = wavelength * wavelength;
+
+        match self {
+            Self::Constant(refractive_index) => refractive_index,
+            Self::Cauchy { a, b } => a + b / squared,
+            Self::Sellmeier { b, c } => (1.
+                + (0..3)
+                    .map(|i| b[i] * squared / (squared - c[i]))
+                    .sum::<f32>())
+            .sqrt(),
+        }
+    }
+    /// If `name` starts a refractive index other than a number, a preset, `cauchy` or `sellmeier`
+    pub fn is_named(name: &str) -> bool {
+        matches!(name, "cauchy" | "sellmeier")
+            || Self::PRESETS.iter().any(|&(preset, _)| preset == name)
+    }
+    /// If it differs between wavelengths, so light disperses
+    pub const fn is_dispersive(self) -> bool {
+        !matches!(self, Self::Constant(_))
+    }
+
+    /// Parses a constant refractive index, `bk7|silica|diamond`, `cauchy a b` or `sellmeier b1 b2 b3 c1 c2 c3`
+    /// # Errors
+    /// When the parameters are missing, negative, or the refractive index isn't positive
+    pub fn parse<'a>(
+        split: &mut impl Iterator<Item = &'a str>,
+        value: &str,
+        expected: &'static str,
+    ) -> Result<Self, ParseError> {
+        let first = split.next();
+        let mut number = || {
+            split
+                .next()
+                .and_then(|number| number.parse().ok())
+                .filter(|&number: &f32| number >= 0.)
+                .ok_or_else(|| ParseError::new(expected, value))
+        };
+
+        let refractive_index = match first {
+            Some("cauchy") => Self::Cauchy {
+                a: number()?,
+                b: number()?,
+            },
+            Some("sellmeier") => {
+                let b = [number()?, number()?, number()?];
+                Self::Sellmeier {
+                    b,
+                    c: [number()?, number()?, number()?],
+                }
+            }
+            _ => Self::PRESETS
+                .into_iter()
+                .find(|&(name, _)| Some(name) == first)
+                .map(|(_, preset)| preset)
+                .or_else(|| {
+                    first
+                        .and_then(|number| number.parse().ok())
+                        .map(Self::Constant)
+                })
+                .ok_or_else(|| ParseError::new(expected, value))?,
+        };
+
+        // real glasses have the poles of the sellmeier equation outside the visible range
+        #[expect(clippy::cast_precision_loss)]
+        let mut visible =
+            (0..=40).map(|step| refractive_index.at(Some(MIN_WAVELENGTH + step as f32 * 10.)));
+        if visible.all(|refractive_index| refractive_index > 0. && refractive_index.is_finite()) {
+            Ok(refractive_index)
+        } else {
+            Err(ParseError::new(expected, value))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Result<RefractiveIndex, ParseError> {
+        RefractiveIndex::parse(&mut value.split_whitespace(), value, "a refractive index")
+    }
+
+    #[test]
+    fn refractive_indices() {
+        assert_eq!(parse("1.33"), Ok(RefractiveIndex::Constant(1.33)));
+        assert_eq!(
+            parse("cauchy 1.5 0.004"),
+            Ok(RefractiveIndex::Cauchy { a: 1.5, b: 0.004 })
+        );
+
+        // bk7 is specified by its index at the d line
+        let bk7 = parse("bk7").unwrap();
+        assert!((bk7.at(None) - 1.5168).abs() < 1e-4);
+        assert!(bk7.is_dispersive());
+        assert!(bk7.at(Some(400.)) > bk7.at(Some(700.)));
+
+        for invalid in [
+            "",
+            "glass",
+            "cauchy 1.5",
+            "cauchy -1 0",
+            // a pole at 548 nm
+            "sellmeier 1 0 0 0.3 0 0",
+        ] {
+            assert!(parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn upsampled_colors_convert_back() {
+        for color in [[1., 1., 1.], [0.8, 0.2, 0.1], [0.05, 0.3, 0.9]] {
+            let mut sum = [0.; 3];
+            for step in 0..1000 {
+                #[expect(clippy::cast_precision_loss)]
+                let mut wavelengths = Wavelengths::sample((step as f32 + 0.5) / 1000.);
+                // a dispersive surface keeps it unbiased
+                if step % 2 == 0 {
+                    wavelengths.terminate_secondary();
+                }
+                let rgb = wavelengths.to_rgb(wavelengths.upsample(Color::new(color)));
+                for (sum, channel) in sum.iter_mut().zip(rgb.into_inner()) {
+                    *sum += channel / 1000.;
+                }
+            }
+
+            for (channel, expected) in sum.into_iter().zip(color) {
+                assert!((channel - expected).abs() < 0.02, "{color:?}: {sum:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::{str::FromStr, time::Duration};
+
+use crate::config::ParseError;
+
+/// Ends a render before all of its samples per pixel are done, which then only bound the samples.
+///
+/// Every pixel keeps the average of the samples it got, and the checkpoint of the last full pass allows resuming.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Termination {
+    /// The wall-clock time after which no further chunks are started.
+    /// The first pass always finishes, so every pixel has samples of this render.
+    pub time_limit: Option<TimeLimit>,
+    /// The mean relative error of the pixels, like 0.01 for 1%, below which no further passes are started
+    pub noise_target: Option<f64>,
+}
+
+/// A duration in seconds, minutes or hours, like `90`, `90s`, `20m` or `1.5h`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeLimit(pub Duration);
+impl FromStr for TimeLimit {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        // seconds without a unit
+        let (amount, seconds) = [("h", 60. * 60.), ("m", 60.), ("s", 1.)]
+            .into_iter()
+            .find_map(|(unit, seconds)| Some((value.strip_suffix(unit)?, seconds)))
+            .unwrap_or((value, 1.));
+
+        amount
+            .parse::<f64>()
+            .ok()
+            .and_then(|amount| Duration::try_from_secs_f64(amount * seconds).ok())
+            .map(Self)
+            .ok_or_else(|| ParseError::new("a duration, like 90s, 20m or 1.5h", value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_limits() {
+        for (value, seconds) in [("90", 90.), ("90s", 90.), ("20m", 1200.), ("1.5h", 5400.)] {
+            assert_eq!(
+                value.parse(),
+                Ok(TimeLimit(Duration::from_secs_f64(seconds))),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_time_limits() {
+        for value in ["", "m", "-5s", "5d", "1.5hm", "inf"] {
+            assert!(value.parse::<TimeLimit>().is_err(), "{value}");
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Fixtures shared by the tests of several modules
+
+use std::{env, fs, path::PathBuf, process};
+
+/// A small scene of 4x4 pixels with 8 samples per pixel and explicit screen corners, showing a sphere against the sky
+pub const SCENE: &str = "screen(-1 1 -0.5, 2 0 0, 0 -2 0, 4, 4, 8, 2)
+camera(0 0 -5)
+spheres((0 0 0, 1, lambertian, 0.5 0.5 0.5))
+planes()
+triangles()";
+
+/// A directory for the files of a test, removed with its contents when dropped
+pub struct TempDir(PathBuf);
+impl TempDir {
+    /// An empty directory, unique to the test `name` and this process
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("raytracer-{name}-{}", process::id()));
+        // the leftovers of a crashed run
+        _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        Self(path)
+    }
+    /// The path of `name` in the directory
+    pub fn join(&self, name: &str) -> String {
+        self.0.join(name).display().to_string()
+    }
+    /// Writes `contents` to `name` in the directory, returning its path
+    pub fn write(&self, name: &str, contents: impl AsRef<[u8]>) -> String {
+        let path = self.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        _ = fs::remove_dir_all(&self.0);
+    }
+}
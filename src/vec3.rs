@@ -7,7 +7,7 @@ use std::{
     str::FromStr,
 };
 
-use crate::{convert::Convert, rng::Random};
+use crate::{config::ParseError, convert::Convert, rng::Random};
 
 // I know this is all way to generic, but its fun :D
 
@@ -372,9 +372,9 @@ impl<const DIMENSIONS: usize, T: Float> Vector<DIMENSIONS, T> {
     #[inline(always)]
     pub fn is_normalized(&self) -> bool
     where
-        f16: Convert<T>,
+        f32: Convert<T>,
     {
-        const TOLERANCE: f16 = 1e-5;
+        const TOLERANCE: f32 = 1e-5;
         self.length() <= (1. + TOLERANCE).convert() && self.length() >= (1. - TOLERANCE).convert()
     }
     #[inline(always)]
@@ -521,7 +521,7 @@ impl<const DIMENSIONS: usize, N: Natural> Color<DIMENSIONS, N> {
 }
 impl<const DIMENSIONS: usize, F: Float> Color<DIMENSIONS, F>
 where
-    f16: Convert<F>,
+    f32: Convert<F>,
 {
     /// Converts float -> natural Colors ( 0.0..1.0 -> 0..MAX).
     #[inline(always)]
@@ -538,11 +538,23 @@ where
     }
 }
 
-impl<Usage: NonNormalVector, T: FromStr<Err: Debug>> From<&str> for BaseVector<3, T, Usage> {
-    fn from(value: &str) -> Self {
-        let mut values = value.split(' ').map(|value| value.parse().unwrap());
+impl<Usage: NonNormalVector, T: FromStr> TryFrom<&str> for BaseVector<3, T, Usage> {
+    type Error = ParseError;
 
-        Self::new(array::from_fn(|_| values.next().unwrap()))
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let error = || ParseError::new("three numbers separated by spaces", value);
+
+        let mut values = value.split(' ').map(str::parse);
+
+        let parsed = [values.next(), values.next(), values.next()];
+        if values.next().is_some() {
+            return Err(error());
+        }
+
+        match parsed {
+            [Some(Ok(x)), Some(Ok(y)), Some(Ok(z))] => Ok(Self::new([x, y, z])),
+            _ => Err(error()),
+        }
     }
 }
 
@@ -576,7 +588,7 @@ impl<const DIMENSIONS: usize, T> NormalizedVector<DIMENSIONS, T> {
 }
 impl<const DIMENSIONS: usize, T: Float> New<[T; DIMENSIONS]> for NormalizedVector<DIMENSIONS, T>
 where
-    f16: Convert<T>,
+    f32: Convert<T>,
 {
     #[inline(always)]
     fn new(input: [T; DIMENSIONS]) -> Self {
@@ -592,7 +604,7 @@ where
 impl<const DIMENSIONS: usize, T: Float> New<Vector<DIMENSIONS, T>>
     for NormalizedVector<DIMENSIONS, T>
 where
-    f16: Convert<T>,
+    f32: Convert<T>,
 {
     #[inline(always)]
     fn new(input: Vector<DIMENSIONS, T>) -> Self {